@@ -1,53 +1,219 @@
 use actix_web::{web, App, HttpServer, Responder, HttpResponse, HttpRequest};
-use crate::fetch_contribution_stats;
+use actix_web::dev::Service;
+use actix_web::http::StatusCode;
+use crate::{fetch_contribution_stats, Account, DateRange, StreakPolicy};
 use crate::config::Config;
 use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::collections::HashMap;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 use lazy_static::lazy_static;
+use chrono::{Datelike, NaiveDate};
+use chrono_tz::Tz;
 use serde::{Serialize, Deserialize};
 use askama::Template;
-use crate::color;
-use crate::templates::{ContributionStatsTemplate, ContributionSvgGraphTemplate, ContributionGraphHtmlTemplate, GraphCell};
+use crate::color::{self, Color, ColorVision, Interpolation};
+use crate::contrast;
+use crate::export::{self, ExportFormat};
+use crate::json_api::{self, ErrorResponse};
+use crate::raster::{self, RasterFormat, RasterSize};
+use crate::theme;
+use crate::tiles::{self, StatsLayout};
+use crate::leaderboard::{self, LeaderboardMetric};
+use crate::metrics;
+use crate::levels::{self, Encoding, Scale};
+use crate::templates::{ContributionStatsTemplate, ContributionSvgGraphTemplate, ContributionGraphHtmlTemplate, ContributionYearsGraphTemplate, GraphCell, YearGraph, LevelPattern, LeaderboardTemplate, TeamStatsTemplate};
 use log::{info, error};
+use futures::future::join_all;
 
 lazy_static! {
     static ref MEMORY_CACHE: Mutex<HashMap<String, (crate::ContributionStats, u64)>> = Mutex::new(HashMap::new());
+    static ref RENDER_CACHE: Mutex<HashMap<String, (Vec<u8>, u64)>> = Mutex::new(HashMap::new());
 }
 
+/// Most years the stacked graph shows at once.
+const MAX_YEARS: i32 = 10;
+
+/// Most users a leaderboard or merged graph lists, since each one is a separate upstream fetch.
+const MAX_USERS: usize = 30;
+
+/// Most bitmaps and bytes kept in the render cache, whatever the cache duration.
+const MAX_RENDER_CACHE_ENTRIES: usize = 256;
+const MAX_RENDER_CACHE_BYTES: usize = 64 * 1024 * 1024;
+
+/// Parameters that change how a bitmap looks, besides the user, range, size and format.
+/// Anything else in the query is left out of the render cache key.
+const RASTER_GRAPH_PARAMS: [&str; 19] = [
+    "background-color", "encoding", "font-size", "interpolation", "label-color", "levels",
+    "min-cell-contrast", "min-contrast", "palette", "primary-color", "scale", "show-months",
+    "show-weekdays", "simulate", "svg-height", "theme", "thresholds", "transition-hue",
+    "widget-background-color",
+];
+
+/// Numbers each rendered graph, so pattern ids stay unique with several graphs on one page.
+static GRAPH_ID: AtomicUsize = AtomicUsize::new(0);
+
 #[derive(Serialize, Deserialize)]
 struct FileCache(HashMap<String, (crate::ContributionStats, u64)>);
 
+/// Colors of a graph, resolved from the query parameters.
+struct GraphColors {
+    primary_color: String,
+    level_count: usize,
+    color_shades: Vec<String>,
+    label_color: String,
+    widget_background: Option<Color>,
+    min_contrast: f32,
+    min_cell_contrast: f32,
+}
+
+fn parse_color_param(params: &HashMap<String, String>, key: &str) -> Result<Option<Color>, String> {
+    params.get(key)
+        .map(|value| Color::parse(value).map_err(|e| format!("Invalid {}: {}", key, e)))
+        .transpose()
+}
+
+fn parse_f32_param(params: &HashMap<String, String>, key: &str, default: f32) -> Result<f32, String> {
+    params.get(key)
+        .map(|value| value.parse::<f32>().map_err(|_| format!("Invalid {} '{}'", key, value)))
+        .transpose()
+        .map(|value| value.unwrap_or(default))
+}
+
+fn prepare_graph_colors(params: &HashMap<String, String>, config: &Config) -> Result<GraphColors, String> {
+    let primary_color = params.get("primary-color").cloned().unwrap_or_else(|| config.default_fg.clone());
+    let bg_color = params.get("background-color").cloned().unwrap_or_else(|| config.default_bg.clone());
+    let transition_hue = params.get("transition-hue").and_then(|v| v.parse::<bool>().ok()).unwrap_or(config.default_transition_hue);
+    let interpolation = match params.get("interpolation") {
+        Some(value) => Interpolation::parse(value).ok_or_else(|| format!("Unknown interpolation '{}'", value))?,
+        None => config.default_interpolation,
+    };
+    let level_count = parse_level_count(params, config)?;
+    let mut widget_background = parse_color_param(params, "widget-background-color")?;
+    let label_color = parse_color_param(params, "label-color")?;
+    let min_contrast = parse_f32_param(params, "min-contrast", config.default_min_contrast)?;
+    let min_cell_contrast = parse_f32_param(params, "min-cell-contrast", config.default_min_cell_contrast)?;
+    let simulate = params.get("simulate")
+        .map(|value| ColorVision::parse(value).ok_or_else(|| format!("Unknown simulate '{}'", value)))
+        .transpose()?;
+
+    // The default theme only applies to widgets that don't pick their own colors
+    let theme_name = params.get("theme").cloned().or_else(|| {
+        let has_colors = params.contains_key("primary-color") || params.contains_key("background-color");
+        if has_colors { None } else { config.default_theme.clone() }
+    });
+    // An explicit palette wins over a named theme, which wins over primary/background colors
+    let mut color_shades = if let Some(palette) = params.get("palette") {
+        let stops = parse_palette(color::split_color_list(palette).into_iter()).map_err(|e| format!("Invalid palette: {}", e))?;
+        color::interpolate_palette(&stops, level_count, interpolation)
+    } else if let Some(name) = theme_name {
+        if name == theme::GLANCE_THEME {
+            color::css_variable_shades(level_count, interpolation)
+        } else {
+            let stops = theme::get(&name).ok_or_else(|| format!("Unknown theme '{}'", name))?;
+            let stops = parse_palette(stops.iter().map(String::as_str)).map_err(|e| format!("Invalid theme '{}': {}", name, e))?;
+            color::interpolate_palette(&stops, level_count, interpolation)
+        }
+    } else {
+        let primary = Color::parse(&primary_color).map_err(|e| format!("Invalid primary-color: {}", e))?;
+        let background = Color::parse(&bg_color).map_err(|e| format!("Invalid background-color: {}", e))?;
+        color::derive_color_shades_with_bg(primary, background, transition_hue, level_count, interpolation)
+    };
+
+    // Knowing what the graph sits on, keep the labels readable and empty days visible.
+    // CSS variable shades are only known in the browser, so they're left alone.
+    let mut label_color = match (widget_background, label_color) {
+        (Some(background), label) => color::ensure_contrast(label.unwrap_or(background), background, min_contrast).to_hex(),
+        (None, Some(label)) => label.to_hex(),
+        (None, None) => "var(--color-text-base)".to_string(),
+    };
+    if let Some(background) = widget_background
+        && let Ok(empty) = Color::parse(&color_shades[0]) {
+        color_shades[0] = color::ensure_contrast(empty, background, min_cell_contrast).to_hex();
+    }
+
+    // Previews the finished colors as a colorblind viewer would see them
+    if let Some(vision) = simulate {
+        // CSS variable shades are only resolved in the browser, out of reach of the simulation
+        if color_shades.iter().any(|shade| Color::parse(shade).is_err()) {
+            return Err("simulate doesn't work with the glance theme, pick another theme or colors to preview".to_string());
+        }
+        for shade in color_shades.iter_mut().chain(std::iter::once(&mut label_color)) {
+            if let Ok(parsed) = Color::parse(shade) {
+                *shade = color::simulate(parsed, vision).to_hex();
+            }
+        }
+        widget_background = widget_background.map(|background| color::simulate(background, vision));
+    }
+
+    Ok(GraphColors {
+        primary_color,
+        level_count,
+        color_shades,
+        label_color,
+        widget_background,
+        min_contrast,
+        min_cell_contrast,
+    })
+}
+
+fn parse_level_count(params: &HashMap<String, String>, config: &Config) -> Result<usize, String> {
+    match params.get("levels") {
+        Some(value) => levels::parse_levels(value),
+        None => Ok(config.default_levels),
+    }
+}
+
+/// Cutoffs between the levels, from explicit `thresholds` or else a `scale`.
+fn resolve_thresholds(stats: &crate::ContributionStats, params: &HashMap<String, String>, config: &Config, level_count: usize) -> Result<Vec<u32>, String> {
+    let scale = match params.get("scale") {
+        Some(value) => Scale::parse(value).ok_or_else(|| format!("Unknown scale '{}'", value))?,
+        // The fixed cutoffs don't stretch to other level counts, so bucket like GitHub instead
+        None if config.default_scale == Scale::Fixed && level_count != levels::FIXED_LEVELS => Scale::Quartile,
+        None => config.default_scale,
+    };
+    if scale == Scale::Fixed && level_count != levels::FIXED_LEVELS {
+        return Err(format!("The fixed scale needs {} levels", levels::FIXED_LEVELS));
+    }
+    match params.get("thresholds") {
+        Some(value) => levels::parse_thresholds(value, level_count),
+        None => Ok(levels::thresholds(scale, stats, level_count)),
+    }
+}
+
 fn prepare_graph_template_data<'a>(
     stats: &'a crate::ContributionStats,
     params: &HashMap<String, String>,
     config: &Config
-) -> ContributionSvgGraphTemplate<'a> {
-    let primary_color = params.get("primary-color").cloned().unwrap_or_else(|| config.default_fg.clone());
-    let bg_color = params.get("background-color").cloned().unwrap_or_else(|| config.default_bg.clone());
+) -> Result<ContributionSvgGraphTemplate<'a>, String> {
     let svg_height = params.get("svg-height").cloned().unwrap_or_else(|| config.default_svg_height.clone());
     let show_months = params.get("show-months").and_then(|v| v.parse::<bool>().ok()).unwrap_or(config.default_show_months);
     let show_weekdays = params.get("show-weekdays").and_then(|v| v.parse::<bool>().ok()).unwrap_or(config.default_show_weekdays);
-    let transition_hue = params.get("transition-hue").and_then(|v| v.parse::<bool>().ok()).unwrap_or(config.default_transition_hue);
     let font_size = params.get("font-size").cloned().unwrap_or_else(|| config.default_font_size.clone());
+    let GraphColors { primary_color, level_count, color_shades, label_color, .. } = prepare_graph_colors(params, config)?;
+    let thresholds = resolve_thresholds(stats, params, config, level_count)?;
 
     let max_count = stats.daily_contributions.iter().map(|(_, c, _)| *c).max().unwrap_or(0);
     let max_rows = 7;
-    let color_shades = color::derive_color_shades_with_bg(&primary_color, &bg_color, transition_hue);
+    let encoding = match params.get("encoding") {
+        Some(value) => Encoding::parse(value).ok_or_else(|| format!("Unknown encoding '{}'", value))?,
+        None => config.default_encoding,
+    };
+    // Calendars for a chosen range can start mid-week, so shift the first day onto its weekday row
+    let first_row = stats.daily_contributions.first()
+        .and_then(|(date, _, _)| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok())
+        .map(|date| date.weekday().num_days_from_sunday() as usize)
+        .unwrap_or(0);
     let cells: Vec<GraphCell> = stats.daily_contributions.iter().enumerate().map(|(i, (date, count, label))| {
-        let col = i / max_rows;
-        let row = i % max_rows;
-        let color = match *count {
-            c if c > 15 => color_shades[4].clone(),
-            c if c > 8 => color_shades[3].clone(),
-            c if c > 4 => color_shades[2].clone(),
-            c if c > 0 => color_shades[1].clone(),
-            _ => color_shades[0].clone(),
-        };
+        let col = (i + first_row) / max_rows;
+        let row = (i + first_row) % max_rows;
+        let level = levels::level(*count, &thresholds);
+        let color = color_shades[level].clone();
         let hover_text = if !label.is_empty() { label.clone() } else { format!("{}: {} contributions", date, count) };
         GraphCell {
             date: date.clone(),
             count: *count,
+            level,
             col,
             row,
             color,
@@ -60,30 +226,211 @@ fn prepare_graph_template_data<'a>(
         if let Ok(ndate) = chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d") {
             let month = ndate.format("%b").to_string();
             if month != last_month {
-                month_labels.push((i / max_rows, month.clone()));
+                month_labels.push(((i + first_row) / max_rows, month.clone()));
                 last_month = month;
             }
         }
     }
-    ContributionSvgGraphTemplate{
+    let weeks = cells.last().map(|cell| cell.col + 1).unwrap_or(0);
+    let patterns = if encoding.is_pattern() { level_patterns(&color_shades, &label_color) } else { Vec::new() };
+    let aria_label = graph_summary(stats);
+    let description = graph_description(stats, encoding, &thresholds, level_count);
+    Ok(ContributionSvgGraphTemplate{
         stats,
         max_count,
         cells,
+        weeks,
         show_months,
         show_weekdays,
         primary_color,
         color_shades,
+        label_color,
         month_labels,
         weekday_labels: config.weekday_labels.clone(),
         svg_height,
         cell_radius: config.cell_radius,
         font_size,
+        encoding,
+        level_marks: level_marks(level_count),
+        patterns,
+        aria_label,
+        description,
+    })
+}
+
+/// Squares for the size encoding, growing from a third of the cell to all of it.
+fn level_marks(level_count: usize) -> Vec<(f32, f32)> {
+    const CELL_SIZE: f32 = 12.0;
+    (0..level_count)
+        .map(|level| {
+            let size = CELL_SIZE * (1.0 + 2.0 * level as f32 / (level_count - 1) as f32) / 3.0;
+            ((CELL_SIZE - size) / 2.0, size)
+        })
+        .collect()
+}
+
+/// Patterns for every level above the background, denser the busier the level.
+/// Each is black or white, whichever stands out more from its shade.
+fn level_patterns(color_shades: &[String], label_color: &str) -> Vec<LevelPattern> {
+    let graph_id = GRAPH_ID.fetch_add(1, Ordering::Relaxed);
+    let steps = (color_shades.len() - 2).max(1) as f32;
+    color_shades.iter().enumerate().skip(1)
+        .map(|(level, shade)| {
+            let color = match Color::parse(shade) {
+                Ok(shade) => {
+                    let (black, white) = (Color::parse("#000").unwrap(), Color::parse("#fff").unwrap());
+                    if color::contrast_ratio(shade, black) >= color::contrast_ratio(shade, white) { "#000000" } else { "#ffffff" }.to_string()
+                },
+                // Only known in the browser, so fall back to the text color
+                Err(_) => label_color.to_string(),
+            };
+            LevelPattern {
+                id: format!("ggg-{}-level-{}", graph_id, level),
+                level,
+                spacing: 6.0 - 3.5 * (level - 1) as f32 / steps,
+                color,
+            }
+        })
+        .collect()
+}
+
+/// One-sentence summary for screen readers.
+fn graph_summary(stats: &crate::ContributionStats) -> String {
+    let total: u32 = stats.daily_contributions.iter().map(|(_, c, _)| c).sum();
+    let first = stats.daily_contributions.first().map(|(date, _, _)| date.as_str()).unwrap_or_default();
+    let last = stats.daily_contributions.last().map(|(date, _, _)| date.as_str()).unwrap_or_default();
+    format!(
+        "{} made {} contributions from {} to {}, with a current streak of {} days and a longest streak of {} days.",
+        stats.username, total, first, last, stats.current_streak, stats.longest_streak,
+    )
+}
+
+/// Explains how to read the graph, including what each level stands for.
+fn graph_description(stats: &crate::ContributionStats, encoding: Encoding, thresholds: &[u32], level_count: usize) -> String {
+    let encoded_by = match encoding {
+        Encoding::Color => "its shade",
+        Encoding::Size => "its shade and the size of its square",
+        Encoding::Dots => "its shade and how dense its dots are",
+        Encoding::Hatch => "its shade and how dense its diagonal lines are",
+    };
+    let levels = (0..level_count)
+        .filter_map(|level| levels::range_label(level, thresholds).map(|range| format!("{} is {}", level, range)))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!(
+        "Contribution graph of {}, one square per day and one column per week, starting on Sunday. \
+        The busier a day, the higher its level, shown by {}. Levels: {}.",
+        stats.username, encoded_by, levels,
+    )
+}
+
+fn parse_palette<'a>(stops: impl Iterator<Item = &'a str>) -> Result<Vec<Color>, String> {
+    let stops = stops.map(Color::parse).collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())?;
+    if stops.len() < 2 {
+        return Err("expected at least 2 colors".to_string());
+    }
+    Ok(stops)
+}
+
+/// What to fetch and how to count it, picked per request.
+#[derive(Debug, Clone, Copy)]
+struct StatsOptions {
+    range: Option<DateRange>,
+    /// Decides which day is today for the viewer.
+    timezone: Tz,
+    streak_policy: StreakPolicy,
+}
+
+impl StatsOptions {
+    fn today(&self) -> NaiveDate {
+        chrono::Utc::now().with_timezone(&self.timezone).date_naive()
     }
 }
 
-fn add_widget_headers(username: &str, builder: &mut actix_web::HttpResponseBuilder) {
-    builder.insert_header(("Widget-Title", "GitHub Contributions"));
-    builder.insert_header(("Widget-Title-URL", format!("https://github.com/{}", username)));
+fn parse_stats_options(params: &HashMap<String, String>, config: &Config) -> Result<StatsOptions, String> {
+    let timezone = match params.get("tz") {
+        Some(value) => value.parse::<Tz>().map_err(|_| format!("Unknown tz '{}'", value))?,
+        None => config.default_timezone,
+    };
+    let streak_policy = match params.get("streak") {
+        Some(value) => StreakPolicy::parse(value).ok_or_else(|| format!("Unknown streak policy '{}', expected strict, grace, skip-N or weekdays", value))?,
+        None => config.default_streak_policy,
+    };
+    Ok(StatsOptions { range: parse_date_range(params)?, timezone, streak_policy })
+}
+
+/// Picks the stats widget's tiles. Quartiles are only shown by default when `tiles` isn't set.
+fn parse_stats_layout(params: &HashMap<String, String>) -> Result<StatsLayout, String> {
+    let tiles = match params.get("tiles") {
+        Some(value) => tiles::parse_tiles(value)?,
+        None => tiles::DEFAULT_TILES.to_vec(),
+    };
+    let labels: Vec<String> = params.get("labels")
+        .map(|value| value.split(',').map(|label| label.trim().to_string()).collect())
+        .unwrap_or_default();
+    if labels.len() > tiles.len() {
+        return Err(format!("Got {} labels for {} tiles", labels.len(), tiles.len()));
+    }
+    let compact = match params.get("layout").map(String::as_str) {
+        None | Some("default") => false,
+        Some("compact") => true,
+        Some(value) => return Err(format!("Unknown layout '{}', expected default or compact", value)),
+    };
+    Ok(StatsLayout {
+        tiles,
+        labels,
+        show_quartiles: params.get("show_quartiles").map(|v| v == "true").unwrap_or(!params.contains_key("tiles")),
+        show_insights: params.get("show_insights").map(|v| v == "true").unwrap_or(false),
+        compact,
+    })
+}
+
+fn parse_date_param(params: &HashMap<String, String>, key: &str) -> Result<Option<NaiveDate>, String> {
+    params.get(key)
+        .map(|value| NaiveDate::parse_from_str(value, "%Y-%m-%d").map_err(|_| format!("Invalid {} '{}', expected YYYY-MM-DD", key, value)))
+        .transpose()
+}
+
+/// Picks the days to fetch from `year`, or `from` and `to`, each defaulting to
+/// a year from the other. `None` means the forge's default rolling year.
+fn parse_date_range(params: &HashMap<String, String>) -> Result<Option<DateRange>, String> {
+    let from = parse_date_param(params, "from")?;
+    let to = parse_date_param(params, "to")?;
+    let year_span = chrono::Duration::days(DateRange::MAX_DAYS - 1);
+    let range = match (params.get("year"), from, to) {
+        (Some(_), Some(_), _) | (Some(_), _, Some(_)) => return Err("year can't be combined with from or to".to_string()),
+        (Some(year), _, _) => {
+            let year = year.parse::<i32>().map_err(|_| format!("Invalid year '{}'", year))?;
+            DateRange::year(year)
+        },
+        (None, Some(from), Some(to)) => DateRange::new(from, to),
+        (None, Some(from), None) => DateRange::new(from, (from + year_span).min(chrono::Utc::now().date_naive())),
+        (None, None, Some(to)) => DateRange::new(to - year_span, to),
+        (None, None, None) => return Ok(None),
+    };
+    range.map(Some).map_err(|e| e.to_string())
+}
+
+/// Parses an account path segment and resolves the instance it lives on.
+/// The instance can be overridden per request with e.g. `gitlab-url`, as long as
+/// its host is allowed by `URL_OVERRIDE_HOSTS`.
+fn resolve_account(value: &str, params: &HashMap<String, String>, config: &Config) -> Result<(Account, String), String> {
+    let account = Account::parse(value).map_err(|e| e.to_string())?;
+    let key = format!("{}-url", account.forge.prefix());
+    let base_url = match params.get(&key) {
+        Some(url) if !config.allows_url_override(account.forge, url) => {
+            return Err(format!("{} '{}' is not allowed, its host must be listed in URL_OVERRIDE_HOSTS", key, url));
+        },
+        Some(url) => url.clone(),
+        None => config.forge_url(account.forge)
+            .ok_or_else(|| format!("No URL configured for {}", account.forge.display_name()))?,
+    };
+    Ok((account, base_url.trim_end_matches('/').to_string()))
+}
+
+fn add_widget_headers(account: &Account, base_url: &str, builder: &mut actix_web::HttpResponseBuilder) {
+    builder.insert_header(("Widget-Title", format!("{} Contributions", account.forge.display_name())));
+    builder.insert_header(("Widget-Title-URL", format!("{}/{}", base_url, account.username)));
     builder.insert_header(("Widget-Content-Type", "html"));
 }
 
@@ -93,19 +440,42 @@ pub async fn run_api_server() -> std::io::Result<()> {
     info!("Starting API server on 0.0.0.0:8080");
     info!("Cache enabled: {}, type: {}, duration: {}s", config.cache_enabled, config.cache_type, config.cache_duration_secs);
 
-    if config.cache_enabled && config.cache_type == "memory" {
+    if let Ok(value) = std::env::var("DATA_SOURCE")
+        && crate::sources::DataSource::parse(&value).is_none() {
+        let e = format!("Unknown DATA_SOURCE '{}', expected auto, graphql or scrape", value);
+        error!("{}", e);
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, e));
+    }
+
+    if let Some(path) = &config.themes_file
+        && let Err(e) = theme::load_file(path) {
+        error!("{}", e);
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()));
+    }
+
+    if config.cache_enabled {
         let config_clone = config.clone();
         tokio::spawn(async move {
             let interval = std::time::Duration::from_secs(60);
             loop {
                 tokio::time::sleep(interval).await;
                 let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
-                let mut cache = MEMORY_CACHE.lock().unwrap();
+                if config_clone.cache_type == "memory" {
+                    let mut cache = MEMORY_CACHE.lock().unwrap();
+                    let before = cache.len();
+                    cache.retain(|_, &mut (_, timestamp)| now - timestamp < config_clone.cache_duration_secs);
+                    let after = cache.len();
+                    if before != after {
+                        info!("Memory cache cleaned: {} -> {} entries", before, after);
+                    }
+                }
+                // Rendered bitmaps are always kept in memory, whatever the cache type
+                let mut cache = RENDER_CACHE.lock().unwrap();
                 let before = cache.len();
-                cache.retain(|_, &mut (_, timestamp)| now - timestamp < config_clone.cache_duration_secs);
+                cache.retain(|_, (_, timestamp)| now - *timestamp < config_clone.cache_duration_secs);
                 let after = cache.len();
                 if before != after {
-                    info!("Memory cache cleaned: {} -> {} entries", before, after);
+                    info!("Render cache cleaned: {} -> {} entries", before, after);
                 }
             }
         });
@@ -113,24 +483,59 @@ pub async fn run_api_server() -> std::io::Result<()> {
 
     HttpServer::new(|| {
         App::new()
+            .wrap_fn(|req, srv| {
+                let route = req.match_pattern().unwrap_or_else(|| "unmatched".to_string());
+                let response = srv.call(req);
+                async move {
+                    let response = response.await?;
+                    metrics::record_request(&route, response.status().as_u16());
+                    Ok(response)
+                }
+            })
+            .route("/metrics", web::get().to(metrics_handler))
             .route("/stats/{username}", web::get().to(stats_handler))
-            .route("/graph_svg/{username}", web::get().to(|path, req| svg_graph_handler(path, req)))
-            .route("/graph/{username}", web::get().to(|path, req| graph_html_handler(path, req)))
+            .route("/api/v1/{username}", web::get().to(json_stats_handler))
+            .route("/export/{username}.{format}", web::get().to(export_handler))
+            .route("/team/{name}/graph", web::get().to(team_graph_handler))
+            .route("/team/{name}/stats", web::get().to(team_stats_handler))
+            .route("/debug/contrast-report", web::get().to(contrast_report_handler))
+            .route("/leaderboard", web::get().to(leaderboard_handler))
+            .route("/graph_svg/merge", web::get().to(merged_svg_graph_handler))
+            .route("/graph/merge", web::get().to(merged_graph_html_handler))
+            .route("/graph_svg/{username}", web::get().to(svg_graph_handler))
+            .route("/graph_png/{username}", web::get().to(|path, req| raster_graph_handler(path, req, RasterFormat::Png)))
+            .route("/graph_webp/{username}", web::get().to(|path, req| raster_graph_handler(path, req, RasterFormat::WebP)))
+            .route("/graph/{username}", web::get().to(graph_html_handler))
     })
     .bind(("0.0.0.0", 8080))?
     .run()
     .await
 }
 
-async fn get_stats(username: &str) -> Result<crate::ContributionStats, String> {
+/// Fetches upstream, recording the latency and any failure in the metrics.
+async fn fetch_stats_timed(account: &Account, base_url: &str, github_token: Option<&str>, range: Option<DateRange>) -> anyhow::Result<crate::ContributionStats> {
+    let started = Instant::now();
+    let result = fetch_contribution_stats(account, base_url, github_token, range).await;
+    metrics::record_fetch(account.forge.prefix(), started.elapsed(), result.is_err());
+    result
+}
+
+/// Fetches the stats of `account`, or takes them from the cache, anchors them on the
+/// viewer's today and counts streaks under their policy.
+async fn get_stats(account: &Account, base_url: &str, options: StatsOptions) -> Result<crate::ContributionStats, String> {
     let config = Config::from_env();
     let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    // Key by profile URL so users on different forges and instances don't collide
+    let mut cache_key = format!("{}/{}", base_url, account.username);
+    if let Some(range) = options.range {
+        cache_key = format!("{}?{}", cache_key, range);
+    }
 
     let stats = if config.cache_enabled {
         if config.cache_type == "memory" {
             if let Some(stats) = {
                 let cache = MEMORY_CACHE.lock().unwrap();
-                cache.get(username).cloned()
+                cache.get(&cache_key).cloned()
             } {
                 if now - stats.1 < config.cache_duration_secs {
                     Some(stats.0)
@@ -143,7 +548,7 @@ async fn get_stats(username: &str) -> Result<crate::ContributionStats, String> {
         } else if config.cache_type == "file" {
             if let Ok(mut file) = std::fs::File::open(&config.cache_file_path) {
                 if let Ok(file_cache) = serde_json::from_reader::<_, FileCache>(&mut file) {
-                    if let Some((stats, timestamp)) = file_cache.0.get(username) {
+                    if let Some((stats, timestamp)) = file_cache.0.get(&cache_key) {
                         if now - *timestamp < config.cache_duration_secs {
                             Some(stats.clone())
                         } else {
@@ -165,14 +570,18 @@ async fn get_stats(username: &str) -> Result<crate::ContributionStats, String> {
         None
     };
 
+    if config.cache_enabled {
+        if stats.is_some() { metrics::record_cache_hit() } else { metrics::record_cache_miss() }
+    }
+
     let stats = match stats {
         Some(stats) => stats,
-        None => match fetch_contribution_stats(username, None).await {
+        None => match fetch_stats_timed(account, base_url, config.graphql_token(base_url), options.range).await {
             Ok(stats) => {
                 if config.cache_enabled {
                     if config.cache_type == "memory" {
                         let mut cache = MEMORY_CACHE.lock().unwrap();
-                        cache.insert(username.to_string(), (stats.clone(), now));
+                        cache.insert(cache_key, (stats.clone(), now));
                     } else if config.cache_type == "file" {
                         let mut cache_map = if let Ok(mut file) = std::fs::File::open(&config.cache_file_path) {
                             if let Ok(file_cache) = serde_json::from_reader::<_, FileCache>(&mut file) {
@@ -183,7 +592,7 @@ async fn get_stats(username: &str) -> Result<crate::ContributionStats, String> {
                         } else {
                             HashMap::new()
                         };
-                        cache_map.insert(username.to_string(), (stats.clone(), now));
+                        cache_map.insert(cache_key, (stats.clone(), now));
                         let file_cache = FileCache(cache_map);
                         if let Ok(mut file) = std::fs::File::create(&config.cache_file_path) {
                             let _ = serde_json::to_writer(&mut file, &file_cache);
@@ -195,7 +604,8 @@ async fn get_stats(username: &str) -> Result<crate::ContributionStats, String> {
             Err(e) => return Err(format!("Error: {}", e)),
         },
     };
-    Ok(stats)
+    let stats = crate::localize_stats(stats, options.today(), options.range);
+    Ok(crate::apply_streak_policy(stats, options.streak_policy, options.today()))
 }
 
 async fn stats_handler(path: web::Path<String>, req: HttpRequest) -> impl Responder {
@@ -203,20 +613,31 @@ async fn stats_handler(path: web::Path<String>, req: HttpRequest) -> impl Respon
     info!("Received /stats request for user: {}", username);
     let query = req.query_string();
     let params: HashMap<_, _> = url::form_urlencoded::parse(query.as_bytes()).into_owned().collect();
-    let show_quartiles = params.get("show_quartiles").map(|v| v == "true").unwrap_or(true);
-    match get_stats(&username).await {
+    let config = Config::from_env();
+    let layout = match parse_stats_layout(&params) {
+        Ok(layout) => layout,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    let (account, base_url) = match resolve_account(&username, &params, &config) {
+        Ok(resolved) => resolved,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    let options = match parse_stats_options(&params, &config) {
+        Ok(options) => options,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    match get_stats(&account, &base_url, options).await {
         Ok(stats) => {
             info!("Successfully got stats for user: {}", username);
             let template = ContributionStatsTemplate { 
-                stats: &stats,
-                show_quartiles,
-                quartiles_string: stats.quartiles.iter().map(|q| q.to_string()).collect::<Vec<_>>().join(" "),
+                rows: layout.rows(&stats),
+                compact: layout.compact,
             };
             match template.render() {
                 Ok(body) => HttpResponse::Ok()
                     .content_type("text/html")
-                    .insert_header(("Widget-Title", "GitHub Stats"))
-                    .insert_header(("Widget-Title-Url", format!("https://github.com/{}", username)))
+                    .insert_header(("Widget-Title", format!("{} Stats", account.forge.display_name())))
+                    .insert_header(("Widget-Title-Url", format!("{}/{}", base_url, account.username)))
                     .insert_header(("Widget-Content-Type", "html"))
                     .body(body),
                 Err(e) => {
@@ -232,23 +653,124 @@ async fn stats_handler(path: web::Path<String>, req: HttpRequest) -> impl Respon
     }
 }
 
+async fn json_stats_handler(path: web::Path<String>, req: HttpRequest) -> impl Responder {
+    let username = path.into_inner();
+    info!("Received /api/v1 request for user: {}", username);
+    let query = req.query_string();
+    let params: HashMap<_, _> = url::form_urlencoded::parse(query.as_bytes()).into_owned().collect();
+    let config = Config::from_env();
+    let (account, base_url) = match resolve_account(&username, &params, &config) {
+        Ok(resolved) => resolved,
+        Err(e) => return HttpResponse::BadRequest().json(ErrorResponse::new(e)),
+    };
+    let level_count = match parse_level_count(&params, &config) {
+        Ok(level_count) => level_count,
+        Err(e) => return HttpResponse::BadRequest().json(ErrorResponse::new(e)),
+    };
+    let options = match parse_stats_options(&params, &config) {
+        Ok(options) => options,
+        Err(e) => return HttpResponse::BadRequest().json(ErrorResponse::new(e)),
+    };
+    match get_stats(&account, &base_url, options).await {
+        Ok(stats) => match resolve_thresholds(&stats, &params, &config, level_count) {
+            Ok(thresholds) => HttpResponse::Ok().json(json_api::stats_response(&stats, level_count, &thresholds)),
+            Err(e) => HttpResponse::BadRequest().json(ErrorResponse::new(e)),
+        },
+        Err(e) => {
+            error!("Failed to get stats for user '{}': {}", username, e);
+            HttpResponse::InternalServerError().json(ErrorResponse::new(e))
+        },
+    }
+}
+
+async fn export_handler(path: web::Path<(String, String)>, req: HttpRequest) -> impl Responder {
+    let (username, extension) = path.into_inner();
+    info!("Received /export request for user: {} as {}", username, extension);
+    let Some(format) = ExportFormat::parse(&extension) else {
+        return HttpResponse::NotFound().body(format!("Unknown export format '{}'", extension));
+    };
+    let query = req.query_string();
+    let params: HashMap<_, _> = url::form_urlencoded::parse(query.as_bytes()).into_owned().collect();
+    let config = Config::from_env();
+    let (account, base_url) = match resolve_account(&username, &params, &config) {
+        Ok(resolved) => resolved,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    let level_count = match parse_level_count(&params, &config) {
+        Ok(level_count) => level_count,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    let options = match parse_stats_options(&params, &config) {
+        Ok(options) => options,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    match get_stats(&account, &base_url, options).await {
+        Ok(stats) => match resolve_thresholds(&stats, &params, &config, level_count) {
+            Ok(thresholds) => HttpResponse::Ok()
+                .content_type(format.content_type())
+                .insert_header(("Content-Disposition", format!("attachment; filename=\"{}\"", format.file_name(&username))))
+                .body(export::export(&stats, &thresholds, format)),
+            Err(e) => HttpResponse::BadRequest().body(e),
+        },
+        Err(e) => {
+            error!("Failed to get stats for user '{}': {}", username, e);
+            HttpResponse::InternalServerError().body(e)
+        },
+    }
+}
+
+fn svg_graph_response(stats: &crate::ContributionStats, params: &HashMap<String, String>, config: &Config, mut builder: actix_web::HttpResponseBuilder) -> HttpResponse {
+    let template = match prepare_graph_template_data(stats, params, config) {
+        Ok(template) => template,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    match template.render() {
+        Ok(body) => builder
+            .content_type("image/svg+xml")
+            .insert_header(("Widget-Content-Type", "html"))
+            .body(body),
+        Err(e) => HttpResponse::InternalServerError().body(format!("Template error: {}", e)),
+    }
+}
+
+/// The graph widget, noting any `failed` users left out of it along with their error.
+fn graph_html_response(stats: &crate::ContributionStats, params: &HashMap<String, String>, config: &Config, failed: Vec<(String, String)>, mut builder: actix_web::HttpResponseBuilder) -> HttpResponse {
+    let svg = match prepare_graph_template_data(stats, params, config) {
+        Ok(svg) => svg,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    let quartiles = svg.stats.quartiles.iter().map(|q| q.to_string()).collect::<Vec<_>>().join(" ");
+    let template = ContributionGraphHtmlTemplate {
+        svg,
+        quartiles,
+        failed,
+    };
+    match template.render() {
+        Ok(body) => builder
+            .content_type("text/html")
+            .body(body),
+        Err(e) => HttpResponse::InternalServerError().body(format!("Template error: {}", e)),
+    }
+}
+
 async fn svg_graph_handler(path: web::Path<String>, req: HttpRequest) -> impl Responder {
     let username = path.into_inner();
     let query = req.query_string();
     let params: HashMap<_, _> = url::form_urlencoded::parse(query.as_bytes()).into_owned().collect();
     let config = Config::from_env();
-    match get_stats(&username).await {
+    let (account, base_url) = match resolve_account(&username, &params, &config) {
+        Ok(resolved) => resolved,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    let options = match parse_stats_options(&params, &config) {
+        Ok(options) => options,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    match get_stats(&account, &base_url, options).await {
         Ok(stats) => {
-            let template = prepare_graph_template_data(&stats, &params, &config);
             let mut builder = HttpResponse::Ok();
-            add_widget_headers(&username, &mut builder);
-            match template.render() {
-                Ok(body) => builder
-                    .content_type("image/svg+xml")
-                    .insert_header(("Widget-Content-Type", "html"))
-                    .body(body),
-                Err(e) => HttpResponse::InternalServerError().body(format!("Template error: {}", e)),
-            }
+            add_widget_headers(&account, &base_url, &mut builder);
+            svg_graph_response(&stats, &params, &config, builder)
         },
         Err(e) => HttpResponse::InternalServerError().body(e),
     }
@@ -259,23 +781,532 @@ async fn graph_html_handler(path: web::Path<String>, req: HttpRequest) -> impl R
     let query = req.query_string();
     let params: HashMap<_, _> = url::form_urlencoded::parse(query.as_bytes()).into_owned().collect();
     let config = Config::from_env();
-    match get_stats(&username).await {
+    let (account, base_url) = match resolve_account(&username, &params, &config) {
+        Ok(resolved) => resolved,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    let options = match parse_stats_options(&params, &config) {
+        Ok(options) => options,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    if let Some(years) = params.get("years") {
+        let years = match parse_years(years) {
+            Ok(years) => years,
+            Err(e) => return HttpResponse::BadRequest().body(e),
+        };
+        let mut builder = HttpResponse::Ok();
+        add_widget_headers(&account, &base_url, &mut builder);
+        return years_graph_response(&account, &base_url, &years, options, &params, &config, builder).await;
+    }
+    match get_stats(&account, &base_url, options).await {
         Ok(stats) => {
-            let svg = prepare_graph_template_data(&stats, &params, &config);
-            let quartiles = svg.stats.quartiles.iter().map(|q| q.to_string()).collect::<Vec<_>>().join(" ");
-            let template = ContributionGraphHtmlTemplate {
+            let mut builder = HttpResponse::Ok();
+            add_widget_headers(&account, &base_url, &mut builder);
+            graph_html_response(&stats, &params, &config, Vec::new(), builder)
+        },
+        Err(e) => HttpResponse::InternalServerError().body(e),
+    }
+}
+
+/// Parses `years` such as `2021-2024`, or a single `2023`, into every year it spans.
+fn parse_years(value: &str) -> Result<Vec<i32>, String> {
+    let (first, last) = value.split_once('-').unwrap_or((value, value));
+    let parse = |year: &str| year.trim().parse::<i32>().map_err(|_| format!("Invalid years '{}', expected e.g. 2021-2024", value));
+    let (first, last) = (parse(first)?, parse(last)?);
+    if first > last || last - first >= MAX_YEARS {
+        return Err(format!("years must span from 1 to {} years, oldest first", MAX_YEARS));
+    }
+    Ok((first..=last).collect())
+}
+
+/// One graph per year, stacked oldest first, with streaks counted across all of them.
+async fn years_graph_response(account: &Account, base_url: &str, years: &[i32], options: StatsOptions, params: &HashMap<String, String>, config: &Config, mut builder: actix_web::HttpResponseBuilder) -> HttpResponse {
+    let ranges = match years.iter().map(|year| DateRange::year(*year)).collect::<Result<Vec<_>, _>>() {
+        Ok(ranges) => ranges,
+        Err(e) => return HttpResponse::BadRequest().body(e.to_string()),
+    };
+    let year_stats = match join_all(ranges.iter().map(|range| get_stats(account, base_url, StatsOptions { range: Some(*range), ..options }))).await
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>() {
+        Ok(year_stats) => year_stats,
+        Err(e) => return HttpResponse::InternalServerError().body(e),
+    };
+    let all_years = crate::apply_streak_policy(crate::combine_ranges(&year_stats), options.streak_policy, options.today());
+
+    // Bucket every year the same way, so their shades can be compared
+    let mut params = params.clone();
+    if !params.contains_key("thresholds") {
+        let thresholds = match parse_level_count(&params, config).and_then(|level_count| resolve_thresholds(&all_years, &params, config, level_count)) {
+            Ok(thresholds) => thresholds,
+            Err(e) => return HttpResponse::BadRequest().body(e),
+        };
+        params.insert("thresholds".to_string(), thresholds.iter().map(u32::to_string).collect::<Vec<_>>().join(","));
+    }
+    let mut graphs = Vec::new();
+    for (year, stats) in years.iter().zip(&year_stats) {
+        let svg = match prepare_graph_template_data(stats, &params, config) {
+            Ok(svg) => svg,
+            Err(e) => return HttpResponse::BadRequest().body(e),
+        };
+        match svg.render() {
+            Ok(svg) => graphs.push(YearGraph {
+                year: *year,
+                total: stats.daily_contributions.iter().map(|(_, c, _)| c).sum(),
                 svg,
-                quartiles,
+            }),
+            Err(e) => return HttpResponse::InternalServerError().body(format!("Template error: {}", e)),
+        }
+    }
+    let template = ContributionYearsGraphTemplate { years: graphs, stats: &all_years };
+    match template.render() {
+        Ok(body) => builder
+            .content_type("text/html")
+            .body(body),
+        Err(e) => HttpResponse::InternalServerError().body(format!("Template error: {}", e)),
+    }
+}
+
+/// Picks the bitmap size from `width`, or else the `pixel-scale` multiplier.
+fn parse_raster_size(params: &HashMap<String, String>) -> Result<RasterSize, String> {
+    if let Some(width) = params.get("width") {
+        return match width.parse::<u32>() {
+            Ok(width) if (1..=raster::MAX_SIZE).contains(&width) => Ok(RasterSize::Width(width)),
+            _ => Err(format!("width must be a number from 1 to {}", raster::MAX_SIZE)),
+        };
+    }
+    match params.get("pixel-scale").map(|value| value.parse::<f32>()) {
+        None => Ok(RasterSize::Scale(1.0)),
+        Some(Ok(scale)) if scale > 0.0 => Ok(RasterSize::Scale(scale)),
+        Some(_) => Err("pixel-scale must be a number above 0".to_string()),
+    }
+}
+
+/// Renders the graph as a bitmap, or the status and message to fail with.
+fn render_raster_graph(stats: &crate::ContributionStats, params: &HashMap<String, String>, size: RasterSize, config: &Config, format: RasterFormat) -> Result<Vec<u8>, (StatusCode, String)> {
+    let template = prepare_graph_template_data(stats, params, config).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    if template.color_shades.iter().any(|shade| Color::parse(shade).is_err()) {
+        return Err((StatusCode::BAD_REQUEST, "The glance theme only works in a browser, pick another theme or colors for a bitmap".to_string()));
+    }
+    let svg = template.render().map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Template error: {}", e)))?;
+    raster::render(&svg, size, format).map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))
+}
+
+/// Identifies a bitmap by the options it was rendered from, so unrelated or
+/// reordered query parameters share one entry.
+fn render_cache_key(account: &Account, base_url: &str, params: &HashMap<String, String>, options: StatsOptions, size: RasterSize, format: RasterFormat) -> String {
+    let graph_params = RASTER_GRAPH_PARAMS.iter()
+        .filter_map(|key| params.get(*key).map(|value| format!("{}={}", key, value)))
+        .collect::<Vec<_>>()
+        .join("&");
+    let range = options.range.map(|range| range.to_string()).unwrap_or_default();
+    format!("{}/{}.{}?range={}&today={}&streak={}&size={:?}&{}", base_url, account.username, format.extension(), range, options.today(), options.streak_policy, size, graph_params)
+}
+
+/// Keeps a rendered bitmap, dropping expired ones and then the oldest to stay
+/// within [`MAX_RENDER_CACHE_ENTRIES`] and [`MAX_RENDER_CACHE_BYTES`].
+fn cache_render(key: String, image: Vec<u8>, now: u64, config: &Config) {
+    let mut cache = RENDER_CACHE.lock().unwrap();
+    cache.retain(|_, (_, timestamp)| now - *timestamp < config.cache_duration_secs);
+    let mut bytes: usize = cache.values().map(|(image, _)| image.len()).sum();
+    while !cache.is_empty() && (cache.len() >= MAX_RENDER_CACHE_ENTRIES || bytes + image.len() > MAX_RENDER_CACHE_BYTES) {
+        let oldest = cache.iter().min_by_key(|(_, (_, timestamp))| *timestamp).map(|(key, _)| key.clone()).unwrap();
+        if let Some((image, _)) = cache.remove(&oldest) {
+            bytes -= image.len();
+        }
+    }
+    if image.len() <= MAX_RENDER_CACHE_BYTES {
+        cache.insert(key, (image, now));
+    }
+}
+
+async fn raster_graph_handler(path: web::Path<String>, req: HttpRequest, format: RasterFormat) -> impl Responder {
+    let username = path.into_inner();
+    let query = req.query_string();
+    let mut params: HashMap<_, _> = url::form_urlencoded::parse(query.as_bytes()).into_owned().collect();
+    let config = Config::from_env();
+    // Glance's text color doesn't exist outside the dashboard
+    if !params.contains_key("label-color") && !params.contains_key("widget-background-color") {
+        params.insert("label-color".to_string(), config.raster_label_color.clone());
+    }
+    let size = match parse_raster_size(&params) {
+        Ok(size) => size,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    let options = match parse_stats_options(&params, &config) {
+        Ok(options) => options,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    let (account, base_url) = match resolve_account(&username, &params, &config) {
+        Ok(resolved) => resolved,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    let cache_key = render_cache_key(&account, &base_url, &params, options, size, format);
+    let cached = RENDER_CACHE.lock().unwrap().get(&cache_key)
+        .filter(|(_, timestamp)| config.cache_enabled && now - timestamp < config.cache_duration_secs)
+        .map(|(image, _)| image.clone());
+    let image = match cached {
+        Some(image) => image,
+        None => {
+            let stats = match get_stats(&account, &base_url, options).await {
+                Ok(stats) => stats,
+                Err(e) => return HttpResponse::InternalServerError().body(e),
             };
+            match render_raster_graph(&stats, &params, size, &config, format) {
+                Ok(image) => {
+                    if config.cache_enabled {
+                        cache_render(cache_key, image.clone(), now, &config);
+                    }
+                    image
+                },
+                Err((status, e)) => return HttpResponse::build(status).body(e),
+            }
+        },
+    };
+    HttpResponse::Ok()
+        .content_type(format.content_type())
+        .body(image)
+}
+
+/// Splits a `users` parameter such as `a,b,gitlab:c`, refusing more than `MAX_USERS`.
+fn split_users(users: &str) -> Result<Vec<&str>, String> {
+    let users: Vec<_> = users.split(',')
+        .map(str::trim)
+        .filter(|user| !user.is_empty())
+        .collect();
+    if users.len() > MAX_USERS {
+        return Err(format!("Got {} users, at most {} are allowed", users.len(), MAX_USERS));
+    }
+    Ok(users)
+}
+
+/// Resolves every entry of a user list such as `a,b,gitlab:c`.
+fn resolve_accounts<'a>(users: impl IntoIterator<Item = &'a str>, params: &HashMap<String, String>, config: &Config) -> Result<Vec<(Account, String)>, String> {
+    users.into_iter()
+        .map(str::trim)
+        .filter(|user| !user.is_empty())
+        .map(|user| resolve_account(user, params, config))
+        .collect()
+}
+
+/// Fetches the stats of every account concurrently, in the order given.
+async fn get_all_stats(accounts: &[(Account, String)], options: StatsOptions) -> Vec<Result<crate::ContributionStats, String>> {
+    join_all(accounts.iter().map(|(account, base_url)| get_stats(account, base_url, options))).await
+}
+
+/// Fetches every account and sums them, failing if any of them can't be fetched.
+/// Returns the merged stats along with each account's own.
+async fn get_merged_stats(accounts: &[(Account, String)], options: StatsOptions) -> Result<(crate::ContributionStats, Vec<crate::ContributionStats>), String> {
+    let all_stats = get_all_stats(accounts, options).await
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()?;
+    let merged = crate::apply_streak_policy(crate::merge_stats(&all_stats), options.streak_policy, options.today());
+    Ok((merged, all_stats))
+}
+
+/// Resolves the `users` to merge, of which there must be at least one.
+fn merge_accounts(params: &HashMap<String, String>, config: &Config) -> Result<Vec<(Account, String)>, String> {
+    let users = params.get("users").map(String::as_str).unwrap_or_default();
+    info!("Received merged graph request for users: {}", users);
+    let accounts = resolve_accounts(split_users(users)?, params, config)?;
+    if accounts.is_empty() {
+        return Err("No users to merge".to_string());
+    }
+    Ok(accounts)
+}
+
+fn add_merged_widget_headers(builder: &mut actix_web::HttpResponseBuilder) {
+    builder.insert_header(("Widget-Title", "Contributions"));
+    builder.insert_header(("Widget-Content-Type", "html"));
+}
+
+async fn merged_svg_graph_handler(req: HttpRequest) -> impl Responder {
+    let query = req.query_string();
+    let params: HashMap<_, _> = url::form_urlencoded::parse(query.as_bytes()).into_owned().collect();
+    let config = Config::from_env();
+    let accounts = match merge_accounts(&params, &config) {
+        Ok(accounts) => accounts,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    let options = match parse_stats_options(&params, &config) {
+        Ok(options) => options,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    match get_merged_stats(&accounts, options).await {
+        Ok((stats, _)) => {
             let mut builder = HttpResponse::Ok();
-            add_widget_headers(&username, &mut builder);
+            add_merged_widget_headers(&mut builder);
+            svg_graph_response(&stats, &params, &config, builder)
+        },
+        Err(e) => HttpResponse::InternalServerError().body(e),
+    }
+}
+
+async fn merged_graph_html_handler(req: HttpRequest) -> impl Responder {
+    let query = req.query_string();
+    let params: HashMap<_, _> = url::form_urlencoded::parse(query.as_bytes()).into_owned().collect();
+    let config = Config::from_env();
+    let accounts = match merge_accounts(&params, &config) {
+        Ok(accounts) => accounts,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    let options = match parse_stats_options(&params, &config) {
+        Ok(options) => options,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    match get_merged_stats(&accounts, options).await {
+        Ok((stats, _)) => {
+            let mut builder = HttpResponse::Ok();
+            add_merged_widget_headers(&mut builder);
+            graph_html_response(&stats, &params, &config, Vec::new(), builder)
+        },
+        Err(e) => HttpResponse::InternalServerError().body(e),
+    }
+}
+
+/// Resolves the members of team `name`, `None` if there's no such team.
+fn team_accounts(name: &str, params: &HashMap<String, String>, config: &Config) -> Option<Result<Vec<(Account, String)>, String>> {
+    let members = config.teams.get(&name.to_lowercase())?;
+    if members.is_empty() {
+        return Some(Err(format!("Team '{}' has no members", name)));
+    }
+    Some(resolve_accounts(members.iter().map(String::as_str), params, config)
+        .map_err(|e| format!("Invalid member of team '{}': {}", name, e)))
+}
+
+/// Fetches every member and sums the ones that loaded, failing only if none did.
+/// Returns the merged stats along with each member's own result.
+async fn get_team_stats(accounts: &[(Account, String)], options: StatsOptions) -> Result<(crate::ContributionStats, Vec<Result<crate::ContributionStats, String>>), String> {
+    let results = get_all_stats(accounts, options).await;
+    let loaded: Vec<_> = results.iter().filter_map(|result| result.as_ref().ok()).cloned().collect();
+    if loaded.is_empty() {
+        let errors = results.into_iter().filter_map(Result::err).collect::<Vec<_>>().join("; ");
+        return Err(format!("No member could be fetched: {}", errors));
+    }
+    let merged = crate::apply_streak_policy(crate::merge_stats(&loaded), options.streak_policy, options.today());
+    Ok((merged, results))
+}
+
+/// Members whose stats couldn't be fetched, with their error.
+fn failed_members(accounts: &[(Account, String)], results: &[Result<crate::ContributionStats, String>]) -> Vec<(String, String)> {
+    accounts.iter()
+        .zip(results)
+        .filter_map(|((account, _), result)| result.as_ref().err().map(|e| (account.to_string(), e.clone())))
+        .collect()
+}
+
+fn add_team_widget_headers(name: &str, title: &str, builder: &mut actix_web::HttpResponseBuilder) {
+    builder.insert_header(("Widget-Title", format!("{} {}", name, title)));
+    builder.insert_header(("Widget-Content-Type", "html"));
+}
+
+async fn team_graph_handler(path: web::Path<String>, req: HttpRequest) -> impl Responder {
+    let name = path.into_inner();
+    info!("Received team graph request for team: {}", name);
+    let query = req.query_string();
+    let params: HashMap<_, _> = url::form_urlencoded::parse(query.as_bytes()).into_owned().collect();
+    let config = Config::from_env();
+    let accounts = match team_accounts(&name, &params, &config) {
+        Some(Ok(accounts)) => accounts,
+        Some(Err(e)) => return HttpResponse::InternalServerError().body(e),
+        None => return HttpResponse::NotFound().body(format!("Unknown team '{}'", name)),
+    };
+    let options = match parse_stats_options(&params, &config) {
+        Ok(options) => options,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    match get_team_stats(&accounts, options).await {
+        Ok((stats, results)) => {
+            let mut builder = HttpResponse::Ok();
+            add_team_widget_headers(&name, "Contributions", &mut builder);
+            graph_html_response(&stats, &params, &config, failed_members(&accounts, &results), builder)
+        },
+        Err(e) => HttpResponse::InternalServerError().body(e),
+    }
+}
+
+async fn team_stats_handler(path: web::Path<String>, req: HttpRequest) -> impl Responder {
+    let name = path.into_inner();
+    info!("Received team stats request for team: {}", name);
+    let query = req.query_string();
+    let params: HashMap<_, _> = url::form_urlencoded::parse(query.as_bytes()).into_owned().collect();
+    let config = Config::from_env();
+    let layout = match parse_stats_layout(&params) {
+        Ok(layout) => layout,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    let top = match params.get("top").map(|v| v.parse::<usize>()) {
+        Some(Ok(top)) if top > 0 => top,
+        Some(_) => return HttpResponse::BadRequest().body(format!("Invalid top '{}', expected a positive number", params["top"])),
+        None => 5,
+    };
+    let accounts = match team_accounts(&name, &params, &config) {
+        Some(Ok(accounts)) => accounts,
+        Some(Err(e)) => return HttpResponse::InternalServerError().body(e),
+        None => return HttpResponse::NotFound().body(format!("Unknown team '{}'", name)),
+    };
+    let options = match parse_stats_options(&params, &config) {
+        Ok(options) => options,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    match get_team_stats(&accounts, options).await {
+        Ok((stats, results)) => {
+            // Members that couldn't be fetched stay listed below the top ones
+            let (mut leaderboard, failed): (Vec<_>, Vec<_>) = leaderboard::rank(&accounts, &results, LeaderboardMetric::Total)
+                .into_iter()
+                .partition(|entry| entry.error.is_none());
+            leaderboard.truncate(top);
+            leaderboard.extend(failed);
+            let template = TeamStatsTemplate {
+                rows: layout.rows(&stats),
+                compact: layout.compact,
+                leaderboard,
+            };
             match template.render() {
-                Ok(body) => builder
-                    .content_type("text/html")
-                    .body(body),
-                Err(e) => HttpResponse::InternalServerError().body(format!("Template error: {}", e)),
+                Ok(body) => {
+                    let mut builder = HttpResponse::Ok();
+                    add_team_widget_headers(&name, "Stats", &mut builder);
+                    builder.content_type("text/html").body(body)
+                },
+                Err(e) => {
+                    error!("Template error for team '{}': {}", name, e);
+                    HttpResponse::InternalServerError().body(format!("Template error: {}", e))
+                },
             }
         },
         Err(e) => HttpResponse::InternalServerError().body(e),
     }
-} 
+}
+
+async fn leaderboard_handler(req: HttpRequest) -> impl Responder {
+    let query = req.query_string();
+    let params: HashMap<_, _> = url::form_urlencoded::parse(query.as_bytes()).into_owned().collect();
+    let config = Config::from_env();
+    let users = params.get("users").cloned().unwrap_or_default();
+    info!("Received leaderboard request for users: {}", users);
+    let metric = match params.get("rank-by") {
+        Some(value) => match LeaderboardMetric::parse(value) {
+            Some(metric) => metric,
+            None => return HttpResponse::BadRequest().body(format!("Unknown rank-by '{}'", value)),
+        },
+        None => LeaderboardMetric::Total,
+    };
+    let users = match split_users(&users) {
+        Ok(users) => users,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    let options = match parse_stats_options(&params, &config) {
+        Ok(options) => options,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    // Users that can't be resolved are listed with their error, like users that can't be fetched
+    let mut accounts = Vec::new();
+    let mut unresolved = Vec::new();
+    for user in users {
+        match resolve_account(user, &params, &config) {
+            Ok(resolved) => accounts.push(resolved),
+            Err(e) => unresolved.push(leaderboard::unresolved(user, e)),
+        }
+    }
+    let results = get_all_stats(&accounts, options).await;
+    let mut entries = leaderboard::rank(&accounts, &results, metric);
+    entries.extend(unresolved);
+    let template = LeaderboardTemplate {
+        metric_label: metric.label(),
+        leaderboard: entries,
+    };
+    match template.render() {
+        Ok(body) => HttpResponse::Ok()
+            .content_type("text/html")
+            .insert_header(("Widget-Title", "Leaderboard"))
+            .insert_header(("Widget-Content-Type", "html"))
+            .body(body),
+        Err(e) => {
+            error!("Template error for leaderboard: {}", e);
+            HttpResponse::InternalServerError().body(format!("Template error: {}", e))
+        },
+    }
+}
+
+async fn metrics_handler() -> impl Responder {
+    let config = Config::from_env();
+    let params = HashMap::new();
+    let accounts = match resolve_accounts(config.metrics_users.iter().map(String::as_str), &params, &config) {
+        Ok(accounts) => accounts,
+        Err(e) => return HttpResponse::InternalServerError().body(format!("Invalid METRICS_USERS: {}", e)),
+    };
+    let options = StatsOptions { range: None, timezone: config.default_timezone, streak_policy: config.default_streak_policy };
+    let results = get_all_stats(&accounts, options).await;
+    let users: Vec<_> = accounts.iter()
+        .map(|(account, _)| account.to_string())
+        .zip(results)
+        .collect();
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4; charset=utf-8")
+        .body(metrics::render(&users))
+}
+
+async fn contrast_report_handler(req: HttpRequest) -> impl Responder {
+    let query = req.query_string();
+    let params: HashMap<_, _> = url::form_urlencoded::parse(query.as_bytes()).into_owned().collect();
+    let config = Config::from_env();
+    match prepare_graph_colors(&params, &config) {
+        Ok(colors) => HttpResponse::Ok().json(contrast::report(
+            &colors.color_shades,
+            &colors.label_color,
+            colors.widget_background,
+            colors.min_contrast,
+            colors.min_cell_contrast,
+        )),
+        Err(e) => HttpResponse::BadRequest().body(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn view_box(first: &str, days: usize, params: &[(&str, &str)]) -> String {
+        let stats = crate::test_support::stats_from(first, &vec![1; days]);
+        let params = params.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        let svg = prepare_graph_template_data(&stats, &params, &Config::from_env()).unwrap().render().unwrap();
+        let start = svg.find("viewBox=\"").unwrap() + "viewBox=\"".len();
+        svg[start..].split('"').next().unwrap().to_string()
+    }
+
+    #[test]
+    fn graph_width_fits_every_week() {
+        // A Sunday-aligned year is 53 weeks
+        assert_eq!(view_box("2023-01-01", 371, &[]), "0 0 775 120");
+        assert_eq!(view_box("2023-01-01", 371, &[("show-weekdays", "false"), ("show-months", "false")]), "0 0 750 105");
+        // 366 days starting on a Saturday spill into a 54th week
+        assert_eq!(view_box("2022-01-01", 366, &[]), "0 0 789 120");
+    }
+
+    #[test]
+    fn render_cache_key_ignores_unrelated_params() {
+        let config = Config::from_env();
+        let account = Account::parse("alice").unwrap();
+        let key = |query: &str| {
+            let params: HashMap<_, _> = url::form_urlencoded::parse(query.as_bytes()).into_owned().collect();
+            let options = parse_stats_options(&params, &config).unwrap();
+            render_cache_key(&account, "https://github.com", &params, options, parse_raster_size(&params).unwrap(), RasterFormat::Png)
+        };
+        assert_eq!(key("theme=nord&levels=4"), key("levels=4&theme=nord&cachebust=1"));
+        assert_ne!(key("theme=nord"), key("theme=dracula"));
+        assert_ne!(key("pixel-scale=2"), key("pixel-scale=3"));
+    }
+
+    #[test]
+    fn render_cache_stays_bounded() {
+        let mut config = Config::from_env();
+        config.cache_duration_secs = 100;
+        for i in 0..MAX_RENDER_CACHE_ENTRIES + 10 {
+            cache_render(format!("bounded-{}", i), vec![0; 16], 1000, &config);
+        }
+        assert_eq!(RENDER_CACHE.lock().unwrap().len(), MAX_RENDER_CACHE_ENTRIES);
+        // Everything above has expired by now, and is dropped on the next insert
+        cache_render("fresh".to_string(), vec![0; 16], 1200, &config);
+        assert_eq!(RENDER_CACHE.lock().unwrap().len(), 1);
+    }
+}
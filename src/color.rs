@@ -1,12 +1,162 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// Color space the shades between two colors are interpolated in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interpolation {
+    /// OKLCH, perceptually even lightness steps along the shortest hue path.
+    Oklch,
+    /// OKLab, perceptually even steps on a straight line without going through hues.
+    Lab,
+    /// HSL along the shortest hue path.
+    Hsl,
+    /// Plain sRGB channel mixing.
+    Rgb,
+}
+
+impl Interpolation {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "oklch" => Some(Interpolation::Oklch),
+            "lab" => Some(Interpolation::Lab),
+            "hsl" => Some(Interpolation::Hsl),
+            "rgb" => Some(Interpolation::Rgb),
+            _ => None,
+        }
+    }
+}
+
+/// A color vision deficiency to preview a palette with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorVision {
+    /// No red cones.
+    Protanopia,
+    /// No green cones, the most common form.
+    Deuteranopia,
+    /// No blue cones.
+    Tritanopia,
+}
+
+impl ColorVision {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "protanopia" => Some(ColorVision::Protanopia),
+            "deuteranopia" => Some(ColorVision::Deuteranopia),
+            "tritanopia" => Some(ColorVision::Tritanopia),
+            _ => None,
+        }
+    }
+
+    /// Machado et al. (2009) simulation matrix at full severity, applied to linear RGB.
+    fn matrix(&self) -> [[f64; 3]; 3] {
+        match self {
+            ColorVision::Protanopia => [
+                [0.152286, 1.052583, -0.204868],
+                [0.114503, 0.786281, 0.099216],
+                [-0.003882, -0.048116, 1.051998],
+            ],
+            ColorVision::Deuteranopia => [
+                [0.367322, 0.860646, -0.227968],
+                [0.280085, 0.672501, 0.047413],
+                [-0.011820, 0.042940, 0.968881],
+            ],
+            ColorVision::Tritanopia => [
+                [1.255528, -0.076749, -0.178779],
+                [-0.078411, 0.930809, 0.147602],
+                [0.004733, 0.691367, 0.303900],
+            ],
+        }
+    }
+}
+
+/// An sRGB color with alpha, every channel in `0.0..=1.0`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Color {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+#[derive(Debug, Clone)]
+pub struct ParseColorError {
+    value: String,
+}
+
+impl fmt::Display for ParseColorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid color '{}'", self.value)
+    }
+}
+
+impl std::error::Error for ParseColorError {}
+
+impl Color {
+    /// Parses any CSS Color Level 4 syntax: hex with 3, 4, 6 or 8 digits, named colors,
+    /// `rgb()`, `hsl()`, `hwb()`, `lab()`, `lch()`, `oklab()` and `oklch()`.
+    pub fn parse(value: &str) -> Result<Self, ParseColorError> {
+        let color = csscolorparser::parse(value.trim())
+            .map_err(|_| ParseColorError { value: value.to_string() })?;
+        Ok(Self { r: color.r, g: color.g, b: color.b, a: color.a })
+    }
+
+    pub fn rgb(&self) -> (f32, f32, f32) {
+        (self.r, self.g, self.b)
+    }
+
+    fn with_rgb((r, g, b): (f32, f32, f32), a: f32) -> Self {
+        Self { r, g, b, a }
+    }
+
+    /// Formats as `#rrggbb`, or `#rrggbbaa` when translucent, clipping anything out of gamut.
+    pub fn to_hex(&self) -> String {
+        let channel = |c: f32| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+        let hex = format!("#{:02x}{:02x}{:02x}", channel(self.r), channel(self.g), channel(self.b));
+        if self.a < 1.0 { format!("{}{:02x}", hex, channel(self.a)) } else { hex }
+    }
+}
+
+impl FromStr for Color {
+    type Err = ParseColorError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Self::parse(value)
+    }
+}
+
+/// Splits a comma-separated list of colors, leaving the commas inside e.g. `rgb(1, 2, 3)` alone.
+pub fn split_color_list(list: &str) -> Vec<&str> {
+    let mut colors = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (i, c) in list.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                colors.push(list[start..i].trim());
+                start = i + 1;
+            },
+            _ => {},
+        }
+    }
+    colors.push(list[start..].trim());
+    colors
+}
+
 pub fn hsl_string(h: f32, s: f32, l: f32) -> String {
     format!("hsl({:.0}, {:.0}%, {:.0}%)", h, s * 100.0, l * 100.0)
 }
 
-pub fn hex_to_hsl(hex: &str) -> Result<(f32, f32, f32), ()> {
-    let (r, g, b) = hex_to_rgb(hex)?;
-    let r = r as f32 / 255.0;
-    let g = g as f32 / 255.0;
-    let b = b as f32 / 255.0;
+fn hsla_string(h: f32, s: f32, l: f32, a: f32) -> String {
+    if a < 1.0 {
+        format!("hsla({:.0}, {:.0}%, {:.0}%, {:.2})", h, s * 100.0, l * 100.0, a)
+    } else {
+        hsl_string(h, s, l)
+    }
+}
+
+pub fn rgb_to_hsl((r, g, b): (f32, f32, f32)) -> (f32, f32, f32) {
     let max = r.max(g).max(b);
     let min = r.min(g).min(b);
     let l = (max + min) / 2.0;
@@ -25,46 +175,248 @@ pub fn hex_to_hsl(hex: &str) -> Result<(f32, f32, f32), ()> {
             (r - g) / d + 4.0
         } / 6.0;
     }
-    Ok((h * 360.0, s, l))
+    (h * 360.0, s, l)
+}
+
+fn srgb_to_linear(c: f64) -> f64 {
+    if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
 }
 
-pub fn hex_to_rgb(hex: &str) -> Result<(u8, u8, u8), ()> {
-    let hex = hex.trim_start_matches('#');
-    if hex.len() == 6 {
-        if let Ok(r) = u8::from_str_radix(&hex[0..2], 16) {
-            if let Ok(g) = u8::from_str_radix(&hex[2..4], 16) {
-                if let Ok(b) = u8::from_str_radix(&hex[4..6], 16) {
-                    return Ok((r, g, b));
-                }
-            }
+fn linear_to_srgb(c: f64) -> f64 {
+    if c <= 0.0031308 { c * 12.92 } else { 1.055 * c.powf(1.0 / 2.4) - 0.055 }
+}
+
+/// Converts sRGB channels in `0.0..=1.0` to OKLab.
+pub fn rgb_to_oklab((r, g, b): (f32, f32, f32)) -> (f32, f32, f32) {
+    let (r, g, b) = (srgb_to_linear(r as f64), srgb_to_linear(g as f64), srgb_to_linear(b as f64));
+    let l = (0.4122214708 * r + 0.5363325363 * g + 0.0514459929 * b).cbrt();
+    let m = (0.2119034982 * r + 0.6806995451 * g + 0.1073969566 * b).cbrt();
+    let s = (0.0883024619 * r + 0.2817188376 * g + 0.6299787005 * b).cbrt();
+    (
+        (0.2104542553 * l + 0.7936177850 * m - 0.0040720468 * s) as f32,
+        (1.9779984951 * l - 2.4285922050 * m + 0.4505937099 * s) as f32,
+        (0.0259040371 * l + 0.7827717662 * m - 0.8086757660 * s) as f32,
+    )
+}
+
+/// Converts OKLab to sRGB channels, which may fall outside `0.0..=1.0` when out of gamut.
+pub fn oklab_to_rgb((l, a, b): (f32, f32, f32)) -> (f32, f32, f32) {
+    let (l, a, b) = (l as f64, a as f64, b as f64);
+    let l_ = (l + 0.3963377774 * a + 0.2158037573 * b).powi(3);
+    let m_ = (l - 0.1055613458 * a - 0.0638541728 * b).powi(3);
+    let s_ = (l - 0.0894841775 * a - 1.2914855480 * b).powi(3);
+    (
+        linear_to_srgb(4.0767416621 * l_ - 3.3077115913 * m_ + 0.2309699292 * s_) as f32,
+        linear_to_srgb(-1.2684380046 * l_ + 2.6097574011 * m_ - 0.3413193965 * s_) as f32,
+        linear_to_srgb(-0.0041960863 * l_ - 0.7034186147 * m_ + 1.7076147010 * s_) as f32,
+    )
+}
+
+/// Converts OKLab to OKLCH, with the hue in degrees.
+pub fn oklab_to_oklch((l, a, b): (f32, f32, f32)) -> (f32, f32, f32) {
+    (l, (a * a + b * b).sqrt(), b.atan2(a).to_degrees().rem_euclid(360.0))
+}
+
+pub fn oklch_to_oklab((l, c, h): (f32, f32, f32)) -> (f32, f32, f32) {
+    let h = h.to_radians();
+    (l, c * h.cos(), c * h.sin())
+}
+
+/// WCAG 2 relative luminance, ignoring alpha.
+pub fn relative_luminance(color: Color) -> f32 {
+    let (r, g, b) = (srgb_to_linear(color.r as f64), srgb_to_linear(color.g as f64), srgb_to_linear(color.b as f64));
+    (0.2126 * r + 0.7152 * g + 0.0722 * b) as f32
+}
+
+/// WCAG 2 contrast ratio between two colors, from 1 to 21.
+pub fn contrast_ratio(a: Color, b: Color) -> f32 {
+    let (la, lb) = (relative_luminance(a), relative_luminance(b));
+    (la.max(lb) + 0.05) / (la.min(lb) + 0.05)
+}
+
+/// Moves `color` lighter or darker in OKLab, keeping its hue, until it has at least
+/// `min_ratio` contrast against `background`. Returns it unchanged if it already does.
+/// Goes as far as black or white if the ratio can't be reached.
+pub fn ensure_contrast(color: Color, background: Color, min_ratio: f32) -> Color {
+    if contrast_ratio(color, background) >= min_ratio {
+        return color;
+    }
+    let (l, a, b) = rgb_to_oklab(color.rgb());
+    // Snap to what the hex output can represent, so rounding can't undo the contrast
+    let with_lightness = |l: f32| {
+        let (r, g, b) = oklab_to_rgb((l, a, b));
+        let channel = |c: f32| (c.clamp(0.0, 1.0) * 255.0).round() / 255.0;
+        Color::with_rgb((channel(r), channel(g), channel(b)), color.a)
+    };
+    // Head towards whichever end can get further from the background
+    let target = if contrast_ratio(with_lightness(1.0), background) >= contrast_ratio(with_lightness(0.0), background) { 1.0 } else { 0.0 };
+    if contrast_ratio(with_lightness(target), background) < min_ratio {
+        return with_lightness(target);
+    }
+    let (mut near, mut far) = (l, target);
+    for _ in 0..20 {
+        let mid = (near + far) / 2.0;
+        if contrast_ratio(with_lightness(mid), background) >= min_ratio {
+            far = mid;
+        } else {
+            near = mid;
         }
     }
-    Err(())
-}
-
-pub fn derive_color_shades_with_bg(primary: &str, bg_color: &str, transition_hue: bool) -> Vec<String> {
-    if let (Ok((h1, s1, l1)), Ok((h2, s2, l2))) = (hex_to_hsl(bg_color), hex_to_hsl(primary)) {
-        let steps = 5;
-        (0..steps)
-            .map(|i| {
-                let t = i as f32 / (steps - 1) as f32;
-                let h = if transition_hue {
-                    h1 + (h2 - h1) * t
-                } else {
-                    if i == 0 { h1 } else { h2 }
-                };
-                let s = s1 + (s2 - s1) * t;
-                let l = l1 + (l2 - l1) * t;
-                hsl_string(h, s, l)
-            })
-            .collect()
-    } else {
-        vec![
-            bg_color.to_string(),
-            "hsl(0, 0%, 70%)".to_string(),
-            "hsl(0, 0%, 50%)".to_string(),
-            "hsl(0, 0%, 35%)".to_string(),
-            "hsl(0, 0%, 20%)".to_string(),
-        ]
-    }
-} 
+    with_lightness(far)
+}
+
+/// Approximates how `color` looks to someone with `vision`.
+pub fn simulate(color: Color, vision: ColorVision) -> Color {
+    let linear = [srgb_to_linear(color.r as f64), srgb_to_linear(color.g as f64), srgb_to_linear(color.b as f64)];
+    let [r, g, b] = vision.matrix().map(|row| {
+        let c = row[0] * linear[0] + row[1] * linear[1] + row[2] * linear[2];
+        linear_to_srgb(c.clamp(0.0, 1.0)) as f32
+    });
+    Color::with_rgb((r, g, b), color.a)
+}
+
+/// Interpolates between two hues in degrees the short way around the wheel.
+fn lerp_hue(h1: f32, h2: f32, t: f32) -> f32 {
+    let delta = (h2 - h1 + 540.0).rem_euclid(360.0) - 180.0;
+    (h1 + delta * t).rem_euclid(360.0)
+}
+
+/// A gray has no meaningful hue, so take the other color's instead of sweeping through the wheel.
+fn hue_pair(h1: f32, chroma1: f32, h2: f32, chroma2: f32) -> (f32, f32) {
+    const ACHROMATIC: f32 = 1e-3;
+    match (chroma1 < ACHROMATIC, chroma2 < ACHROMATIC) {
+        (true, false) => (h2, h2),
+        (false, true) => (h1, h1),
+        _ => (h1, h2),
+    }
+}
+
+/// Mixes two colors, `t` of the way from `from` to `to`. With `keep_hue`,
+/// hue-based spaces keep the hue of `to` instead of transitioning to it.
+fn mix(from: Color, to: Color, t: f32, interpolation: Interpolation, keep_hue: bool) -> String {
+    let lerp = |a: f32, b: f32| a + (b - a) * t;
+    let alpha = lerp(from.a, to.a);
+    match interpolation {
+        Interpolation::Hsl => {
+            let ((h1, s1, l1), (h2, s2, l2)) = (rgb_to_hsl(from.rgb()), rgb_to_hsl(to.rgb()));
+            let (h1, h2) = hue_pair(h1, s1, h2, s2);
+            let h = if keep_hue { h2 } else { lerp_hue(h1, h2, t) };
+            hsla_string(h, lerp(s1, s2), lerp(l1, l2), alpha)
+        },
+        Interpolation::Oklch => {
+            let (l1, c1, h1) = oklab_to_oklch(rgb_to_oklab(from.rgb()));
+            let (l2, c2, h2) = oklab_to_oklch(rgb_to_oklab(to.rgb()));
+            let (h1, h2) = hue_pair(h1, c1, h2, c2);
+            let h = if keep_hue { h2 } else { lerp_hue(h1, h2, t) };
+            Color::with_rgb(oklab_to_rgb(oklch_to_oklab((lerp(l1, l2), lerp(c1, c2), h))), alpha).to_hex()
+        },
+        Interpolation::Lab => {
+            let ((l1, a1, b1), (l2, a2, b2)) = (rgb_to_oklab(from.rgb()), rgb_to_oklab(to.rgb()));
+            Color::with_rgb(oklab_to_rgb((lerp(l1, l2), lerp(a1, a2), lerp(b1, b2))), alpha).to_hex()
+        },
+        Interpolation::Rgb => {
+            Color::with_rgb((lerp(from.r, to.r), lerp(from.g, to.g), lerp(from.b, to.b)), alpha).to_hex()
+        },
+    }
+}
+
+pub fn derive_color_shades_with_bg(primary: Color, bg_color: Color, transition_hue: bool, steps: usize, interpolation: Interpolation) -> Vec<String> {
+    (0..steps)
+        .map(|i| {
+            let t = i as f32 / (steps - 1) as f32;
+            // Without transition_hue only the background keeps its own hue
+            mix(bg_color, primary, t, interpolation, !transition_hue && i > 0)
+        })
+        .collect()
+}
+
+/// Shades mixed at render time from Glance's `--color-primary` and widget background,
+/// so the graph follows the dashboard theme, including live light/dark switches.
+pub fn css_variable_shades(steps: usize, interpolation: Interpolation) -> Vec<String> {
+    let space = match interpolation {
+        Interpolation::Oklch => "oklch",
+        Interpolation::Lab => "oklab",
+        Interpolation::Hsl => "hsl",
+        Interpolation::Rgb => "srgb",
+    };
+    let background = "var(--color-widget-background-highlight, var(--color-widget-background))";
+    (0..steps)
+        .map(|i| match i {
+            0 => background.to_string(),
+            i => {
+                let percent = (i as f32 / (steps - 1) as f32 * 100.0).round();
+                format!("color-mix(in {}, var(--color-primary) {}%, {})", space, percent, background)
+            },
+        })
+        .collect()
+}
+
+/// Spreads a multi-stop palette over `steps` shades, interpolating between
+/// neighbouring stops. Needs at least two stops.
+pub fn interpolate_palette(stops: &[Color], steps: usize, interpolation: Interpolation) -> Vec<String> {
+    if stops.len() == steps {
+        return stops.iter().map(Color::to_hex).collect();
+    }
+    let segments = stops.len() - 1;
+    (0..steps)
+        .map(|i| {
+            let position = i as f32 / (steps - 1) as f32 * segments as f32;
+            let segment = (position.floor() as usize).min(segments - 1);
+            mix(stops[segment], stops[segment + 1], position - segment as f32, interpolation, false)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close((a1, a2, a3): (f32, f32, f32), (b1, b2, b3): (f32, f32, f32)) {
+        let close = |a: f32, b: f32| (a - b).abs() < 1e-3;
+        assert!(close(a1, b1) && close(a2, b2) && close(a3, b3), "{:?} != {:?}", (a1, a2, a3), (b1, b2, b3));
+    }
+
+    #[test]
+    fn oklab_matches_reference_values() {
+        assert_close(rgb_to_oklab((1.0, 1.0, 1.0)), (1.0, 0.0, 0.0));
+        assert_close(rgb_to_oklab((0.0, 0.0, 0.0)), (0.0, 0.0, 0.0));
+        assert_close(rgb_to_oklab((1.0, 0.0, 0.0)), (0.628, 0.2249, 0.1258));
+        assert_close(rgb_to_oklab((0.0, 0.0, 1.0)), (0.452, -0.0325, -0.3115));
+    }
+
+    #[test]
+    fn oklab_round_trips_through_rgb_and_oklch() {
+        for rgb in [(0.25, 0.77, 0.39), (0.92, 0.93, 0.94), (0.0, 0.5, 1.0)] {
+            let lab = rgb_to_oklab(rgb);
+            assert_close(oklab_to_rgb(lab), rgb);
+            assert_close(oklch_to_oklab(oklab_to_oklch(lab)), lab);
+        }
+    }
+
+    #[test]
+    fn hues_interpolate_the_short_way_around() {
+        // Either end of the wheel is the same red
+        let hue = lerp_hue(350.0, 10.0, 0.5);
+        assert!(((hue + 180.0).rem_euclid(360.0) - 180.0).abs() < 1e-3, "{}", hue);
+        assert!((lerp_hue(10.0, 350.0, 0.25) - 5.0).abs() < 1e-3);
+        assert!((lerp_hue(90.0, 180.0, 0.5) - 135.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn grays_take_the_other_colors_hue() {
+        assert_eq!(hue_pair(0.0, 0.0, 140.0, 0.2), (140.0, 140.0));
+        assert_eq!(hue_pair(30.0, 0.2, 200.0, 0.0), (30.0, 30.0));
+        assert_eq!(hue_pair(30.0, 0.2, 200.0, 0.1), (30.0, 200.0));
+    }
+
+    #[test]
+    fn palettes_keep_their_end_stops() {
+        let stops = [Color::parse("#ebedf0").unwrap(), Color::parse("#216e39").unwrap()];
+        for interpolation in [Interpolation::Oklch, Interpolation::Lab, Interpolation::Rgb] {
+            let shades = interpolate_palette(&stops, 5, interpolation);
+            assert_eq!(shades.len(), 5);
+            assert_eq!(shades[0], "#ebedf0");
+            assert_eq!(shades[4], "#216e39");
+        }
+    }
+}
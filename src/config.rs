@@ -1,4 +1,10 @@
 use std::env;
+use chrono_tz::Tz;
+use std::collections::HashMap;
+use crate::color::Interpolation;
+use crate::levels::{Encoding, Scale};
+use crate::sources::{DataSource, Forge, gitea, github, gitlab};
+use crate::streaks::StreakPolicy;
 
 #[derive(Debug, Clone)]
 pub struct Config {
@@ -6,6 +12,19 @@ pub struct Config {
     pub cache_type: String,
     pub cache_duration_secs: u64,
     pub cache_file_path: String,
+    pub github_url: String,
+    pub gitlab_url: String,
+    pub gitea_url: Option<String>,
+    pub forgejo_url: Option<String>,
+    pub url_override_hosts: Vec<String>,
+    pub github_token: Option<String>,
+    pub data_source: DataSource,
+    pub default_timezone: Tz,
+    pub default_streak_policy: StreakPolicy,
+    pub teams: HashMap<String, Vec<String>>,
+    pub metrics_users: Vec<String>,
+    pub themes_file: Option<String>,
+    pub default_theme: Option<String>,
     pub default_fg: String,
     pub default_bg: String,
     pub default_svg_height: String,
@@ -14,7 +33,14 @@ pub struct Config {
     pub cell_radius: u32,
     pub weekday_labels: Vec<(usize, &'static str)>,
     pub default_transition_hue: bool,
+    pub default_interpolation: Interpolation,
+    pub default_min_contrast: f32,
+    pub default_min_cell_contrast: f32,
     pub default_font_size: String,
+    pub default_scale: Scale,
+    pub default_levels: usize,
+    pub default_encoding: Encoding,
+    pub raster_label_color: String,
 }
 
 impl Config {
@@ -31,6 +57,56 @@ impl Config {
                 .unwrap_or(3600),
             cache_file_path: env::var("CACHE_FILE_PATH")
                 .unwrap_or_else(|_| "cache.json".to_string()),
+            // Source configuration
+            github_url: env::var("GITHUB_URL")
+                .unwrap_or_else(|_| github::DEFAULT_GITHUB_URL.to_string()),
+            gitlab_url: env::var("GITLAB_URL")
+                .unwrap_or_else(|_| gitlab::DEFAULT_GITLAB_URL.to_string()),
+            gitea_url: env::var("GITEA_URL").ok(),
+            forgejo_url: env::var("FORGEJO_URL").ok(),
+            // Per-widget *-url overrides are refused unless their host is listed here
+            url_override_hosts: env::var("URL_OVERRIDE_HOSTS")
+                .unwrap_or_default()
+                .split(',')
+                .map(|host| host.trim().to_lowercase())
+                .filter(|host| !host.is_empty())
+                .collect(),
+            github_token: env::var("GITHUB_TOKEN")
+                .ok()
+                .filter(|v| !v.is_empty()),
+            // The server refuses to start on an unknown source, scraping is only a
+            // safe fallback so a typo can never send the token anywhere
+            data_source: match env::var("DATA_SOURCE") {
+                Ok(value) => DataSource::parse(&value).unwrap_or(DataSource::Scrape),
+                Err(_) => DataSource::Auto,
+            },
+            default_timezone: env::var("DEFAULT_TZ")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(Tz::UTC),
+            default_streak_policy: env::var("DEFAULT_STREAK_POLICY")
+                .ok()
+                .and_then(|v| StreakPolicy::parse(&v))
+                .unwrap_or_default(),
+            // Teams, one TEAM_<NAME>=user,gitlab:user,... variable each
+            teams: env::vars()
+                .filter_map(|(key, value)| {
+                    let name = key.strip_prefix("TEAM_")?.to_lowercase();
+                    let members = value.split(',')
+                        .map(|member| member.trim().to_string())
+                        .filter(|member| !member.is_empty())
+                        .collect();
+                    Some((name, members))
+                })
+                .collect(),
+            metrics_users: env::var("METRICS_USERS")
+                .unwrap_or_default()
+                .split(',')
+                .map(|user| user.trim().to_string())
+                .filter(|user| !user.is_empty())
+                .collect(),
+            themes_file: env::var("THEMES_FILE").ok(),
+            default_theme: env::var("DEFAULT_THEME").ok().filter(|v| !v.is_empty()),
             default_fg: "#40c463".to_string(),
             default_bg: "#ebedf0".to_string(),
             default_svg_height: "110".to_string(),
@@ -39,7 +115,103 @@ impl Config {
             cell_radius: 2,
             weekday_labels: vec![(1, "Mon"), (3, "Wed"), (5, "Fri")],
             default_transition_hue: false,
+            default_interpolation: Interpolation::Hsl,
+            // WCAG AA for text, and a faint but visible edge for empty cells
+            default_min_contrast: 4.5,
+            default_min_cell_contrast: 1.15,
             default_font_size: "12".to_string(),
+            default_scale: Scale::Fixed,
+            default_levels: 5,
+            default_encoding: Encoding::Color,
+            // Readable on both white and black, for bitmaps that don't know what they sit on
+            raster_label_color: "#767676".to_string(),
+        }
+    }
+
+    /// Base URL of the instance that hosts `forge`, if one is configured.
+    pub fn forge_url(&self, forge: Forge) -> Option<String> {
+        match forge {
+            Forge::GitHub => Some(self.github_url.clone()),
+            Forge::GitLab => Some(self.gitlab_url.clone()),
+            Forge::Gitea => self.gitea_url.clone(),
+            Forge::Forgejo => self.forgejo_url.clone(),
+            Forge::Codeberg => Some(gitea::CODEBERG_URL.to_string()),
         }
     }
-} 
+
+    /// Whether a widget may point `forge` at `url`: either the configured instance,
+    /// or an http(s) URL on one of `URL_OVERRIDE_HOSTS`.
+    pub fn allows_url_override(&self, forge: Forge, url: &str) -> bool {
+        let url = url.trim_end_matches('/');
+        if self.forge_url(forge).is_some_and(|configured| configured.trim_end_matches('/') == url) {
+            return true;
+        }
+        url::Url::parse(url).ok()
+            .filter(|url| matches!(url.scheme(), "http" | "https"))
+            .and_then(|url| url.host_str().map(str::to_lowercase))
+            .is_some_and(|host| self.url_override_hosts.contains(&host))
+    }
+
+    /// Token to query the GraphQL API on `base_url` with, or `None` to scrape the
+    /// contributions page. The token only ever goes to the configured `GITHUB_URL`,
+    /// so per-widget overrides are scraped anonymously.
+    pub fn graphql_token(&self, base_url: &str) -> Option<&str> {
+        if base_url.trim_end_matches('/') != self.github_url.trim_end_matches('/') {
+            return None;
+        }
+        match self.data_source {
+            DataSource::Scrape => None,
+            DataSource::GraphQl if self.github_token.is_none() => {
+                log::warn!("DATA_SOURCE is graphql but GITHUB_TOKEN is not set, falling back to scraping");
+                None
+            }
+            DataSource::GraphQl | DataSource::Auto => self.github_token.as_deref(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> Config {
+        let mut config = Config::from_env();
+        config.github_url = "https://github.example.com".to_string();
+        config.github_token = Some("secret".to_string());
+        config.data_source = DataSource::Auto;
+        config
+    }
+
+    #[test]
+    fn graphql_token_only_goes_to_the_configured_instance() {
+        let config = config();
+        assert_eq!(config.graphql_token("https://github.example.com"), Some("secret"));
+        assert_eq!(config.graphql_token("https://github.example.com/"), Some("secret"));
+        assert_eq!(config.graphql_token("https://attacker.example"), None);
+        assert_eq!(config.graphql_token("https://github.example.com.attacker.example"), None);
+    }
+
+    #[test]
+    fn graphql_token_follows_the_data_source() {
+        let mut config = config();
+        config.data_source = DataSource::Scrape;
+        assert_eq!(config.graphql_token("https://github.example.com"), None);
+        config.data_source = DataSource::GraphQl;
+        config.github_token = None;
+        assert_eq!(config.graphql_token("https://github.example.com"), None);
+    }
+
+    #[test]
+    fn url_overrides_need_an_allowed_host() {
+        let mut config = config();
+        assert!(config.allows_url_override(Forge::GitHub, "https://github.example.com/"));
+        assert!(!config.allows_url_override(Forge::GitHub, "https://attacker.example"));
+        assert!(!config.allows_url_override(Forge::GitLab, "https://github.example.com"));
+        config.url_override_hosts = vec!["gitlab.example.com".to_string()];
+        assert!(config.allows_url_override(Forge::GitLab, "https://GitLab.example.com"));
+        assert!(config.allows_url_override(Forge::GitHub, "http://gitlab.example.com:8080"));
+        assert!(!config.allows_url_override(Forge::GitLab, "https://gitlab.example.com.attacker.example"));
+        assert!(!config.allows_url_override(Forge::GitLab, "file://gitlab.example.com/etc/passwd"));
+        assert!(!config.allows_url_override(Forge::GitLab, "gitlab.example.com"));
+    }
+}
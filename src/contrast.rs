@@ -0,0 +1,61 @@
+use serde::Serialize;
+use crate::color::{self, Color};
+
+/// How legible a graph's colors are against the surface it sits on.
+#[derive(Debug, Serialize)]
+pub struct ContrastReport {
+    pub widget_background: Option<String>,
+    pub min_contrast: f32,
+    pub min_cell_contrast: f32,
+    pub label: ColorContrast,
+    pub levels: Vec<ColorContrast>,
+}
+
+/// Contrast figures for one color. They're `None` when the color or the
+/// background is only known in the browser, e.g. a CSS variable.
+#[derive(Debug, Serialize)]
+pub struct ColorContrast {
+    pub color: String,
+    pub luminance: Option<f32>,
+    pub contrast_with_background: Option<f32>,
+    /// For levels, the contrast with the level below it.
+    pub contrast_with_previous: Option<f32>,
+    pub passes: Option<bool>,
+}
+
+fn color_contrast(value: &str, previous: Option<&str>, background: Option<Color>, min_ratio: f32) -> ColorContrast {
+    let parsed = Color::parse(value).ok();
+    let contrast_with_background = parsed.zip(background).map(|(c, bg)| color::contrast_ratio(c, bg));
+    ColorContrast {
+        color: value.to_string(),
+        luminance: parsed.map(color::relative_luminance),
+        contrast_with_background,
+        contrast_with_previous: previous
+            .and_then(|previous| Color::parse(previous).ok())
+            .zip(parsed)
+            .map(|(previous, c)| color::contrast_ratio(previous, c)),
+        passes: contrast_with_background.map(|ratio| ratio >= min_ratio),
+    }
+}
+
+/// Checks the label against `min_contrast` and every level against `min_cell_contrast`.
+pub fn report(
+    color_shades: &[String],
+    label_color: &str,
+    widget_background: Option<Color>,
+    min_contrast: f32,
+    min_cell_contrast: f32,
+) -> ContrastReport {
+    ContrastReport {
+        widget_background: widget_background.map(|bg| bg.to_hex()),
+        min_contrast,
+        min_cell_contrast,
+        label: color_contrast(label_color, None, widget_background, min_contrast),
+        levels: color_shades.iter().enumerate()
+            .map(|(i, shade)| {
+                let previous = i.checked_sub(1).map(|p| color_shades[p].as_str());
+                color_contrast(shade, previous, widget_background, min_cell_contrast)
+            })
+            .collect(),
+    }
+}
@@ -0,0 +1,196 @@
+use chrono::{NaiveDate, Utc};
+use crate::ContributionStats;
+use crate::json_api::DailyContribution;
+use crate::levels;
+
+/// File formats the daily contributions can be downloaded in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    Ndjson,
+    Ics,
+}
+
+impl ExportFormat {
+    pub fn parse(extension: &str) -> Option<Self> {
+        match extension {
+            "csv" => Some(ExportFormat::Csv),
+            "ndjson" => Some(ExportFormat::Ndjson),
+            "ics" => Some(ExportFormat::Ics),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Ndjson => "ndjson",
+            ExportFormat::Ics => "ics",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Ndjson => "application/x-ndjson",
+            ExportFormat::Ics => "text/calendar; charset=utf-8",
+        }
+    }
+
+    /// Download name such as `gitlab-alice-contributions.csv`.
+    pub fn file_name(&self, username: &str) -> String {
+        let username: String = username.chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '-' })
+            .collect();
+        format!("{}-contributions.{}", username, self.extension())
+    }
+}
+
+pub fn export(stats: &ContributionStats, thresholds: &[u32], format: ExportFormat) -> String {
+    match format {
+        ExportFormat::Csv => to_csv(stats, thresholds),
+        ExportFormat::Ndjson => to_ndjson(stats, thresholds),
+        ExportFormat::Ics => to_ics(stats),
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// One `date,count,level,label` row per day, with a header.
+pub fn to_csv(stats: &ContributionStats, thresholds: &[u32]) -> String {
+    let mut csv = String::from("date,count,level,label\r\n");
+    for (date, count, label) in &stats.daily_contributions {
+        let level = levels::level(*count, thresholds);
+        csv.push_str(&format!("{},{},{},{}\r\n", csv_field(date), count, level, csv_field(label)));
+    }
+    csv
+}
+
+/// One JSON object per line, shaped like the days of the JSON API.
+pub fn to_ndjson(stats: &ContributionStats, thresholds: &[u32]) -> String {
+    let mut ndjson = String::new();
+    for (date, count, label) in &stats.daily_contributions {
+        let day = DailyContribution { date, count: *count, level: levels::level(*count, thresholds), label };
+        ndjson.push_str(&serde_json::to_string(&day).unwrap_or_default());
+        ndjson.push('\n');
+    }
+    ndjson
+}
+
+fn ics_text(value: &str) -> String {
+    value.replace('\\', "\\\\").replace(';', "\\;").replace(',', "\\,").replace('\n', "\\n")
+}
+
+/// Folds a content line to at most 75 octets per line, as iCalendar requires.
+fn ics_line(ics: &mut String, line: &str) {
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            ics.push_str("\r\n ");
+            width = 1;
+        }
+        ics.push(c);
+        width += c.len_utf8();
+    }
+    ics.push_str("\r\n");
+}
+
+/// An all-day event for every day with contributions.
+pub fn to_ics(stats: &ContributionStats) -> String {
+    let stamp = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+    let mut ics = String::new();
+    ics_line(&mut ics, "BEGIN:VCALENDAR");
+    ics_line(&mut ics, "VERSION:2.0");
+    ics_line(&mut ics, "PRODID:-//glance-github-graph//Contributions//EN");
+    ics_line(&mut ics, "CALSCALE:GREGORIAN");
+    ics_line(&mut ics, &format!("X-WR-CALNAME:{}", ics_text(&format!("{} contributions", stats.username))));
+    for (date, count, label) in &stats.daily_contributions {
+        if *count == 0 {
+            continue;
+        }
+        let Ok(day) = NaiveDate::parse_from_str(date, "%Y-%m-%d") else {
+            continue;
+        };
+        let summary = match count {
+            1 => "1 contribution".to_string(),
+            n => format!("{} contributions", n),
+        };
+        ics_line(&mut ics, "BEGIN:VEVENT");
+        ics_line(&mut ics, &format!("UID:{}-{}@glance-github-graph", date, ics_text(&stats.username)));
+        ics_line(&mut ics, &format!("DTSTAMP:{}", stamp));
+        ics_line(&mut ics, &format!("DTSTART;VALUE=DATE:{}", day.format("%Y%m%d")));
+        if let Some(next) = day.succ_opt() {
+            ics_line(&mut ics, &format!("DTEND;VALUE=DATE:{}", next.format("%Y%m%d")));
+        }
+        ics_line(&mut ics, &format!("SUMMARY:{}", ics_text(&summary)));
+        if !label.is_empty() {
+            ics_line(&mut ics, &format!("DESCRIPTION:{}", ics_text(label)));
+        }
+        ics_line(&mut ics, "TRANSP:TRANSPARENT");
+        ics_line(&mut ics, "END:VEVENT");
+    }
+    ics_line(&mut ics, "END:VCALENDAR");
+    ics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::stats;
+
+    #[test]
+    fn ics_lines_fold_at_75_octets() {
+        let mut ics = String::new();
+        let line = "D".repeat(160);
+        ics_line(&mut ics, &line);
+        let folded: Vec<_> = ics.trim_end_matches("\r\n").split("\r\n").collect();
+        assert_eq!(folded.len(), 3);
+        assert!(folded.iter().all(|part| part.len() <= 75));
+        assert!(folded[1..].iter().all(|part| part.starts_with(' ')));
+        assert_eq!(folded.concat().replace(' ', ""), line);
+    }
+
+    #[test]
+    fn ics_lines_fold_between_characters() {
+        let mut ics = String::new();
+        let line = format!("{}{}", "a".repeat(74), "é".repeat(3));
+        ics_line(&mut ics, &line);
+        let folded: Vec<_> = ics.trim_end_matches("\r\n").split("\r\n").collect();
+        assert_eq!(folded, vec![&"a".repeat(74) as &str, " ééé"]);
+    }
+
+    #[test]
+    fn short_ics_lines_are_left_alone() {
+        let mut ics = String::new();
+        ics_line(&mut ics, &"x".repeat(75));
+        assert_eq!(ics, format!("{}\r\n", "x".repeat(75)));
+    }
+
+    #[test]
+    fn ics_text_escapes_special_characters() {
+        assert_eq!(ics_text("a,b;c\\d\ne"), "a\\,b\\;c\\\\d\\ne");
+    }
+
+    #[test]
+    fn ics_only_has_events_for_days_with_contributions() {
+        let stats = stats(&[("2024-01-01", 0), ("2024-01-02", 3)]);
+        let ics = to_ics(&stats);
+        assert_eq!(ics.matches("BEGIN:VEVENT").count(), 1);
+        assert!(ics.contains("DTSTART;VALUE=DATE:20240102\r\n"));
+        assert!(ics.contains("DTEND;VALUE=DATE:20240103\r\n"));
+        assert!(ics.contains("SUMMARY:3 contributions\r\n"));
+        assert!(ics.ends_with("END:VCALENDAR\r\n"));
+    }
+
+    #[test]
+    fn csv_quotes_fields_with_separators() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a, \"b\""), "\"a, \"\"b\"\"\"");
+    }
+}
@@ -0,0 +1,132 @@
+use chrono::{Datelike, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+const WEEKDAYS: [&str; 7] = ["Sunday", "Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday"];
+
+/// Averages and distributions over a series of daily contributions.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Insights {
+    pub active_days: u32,
+    /// Share of days with contributions, 0 to 100.
+    pub active_day_percentage: f32,
+    pub mean_per_active_day: f32,
+    pub median_per_active_day: f32,
+    /// Contributions per weekday, Sunday first.
+    pub weekday_totals: [u32; 7],
+    /// Full name of the weekday with the most contributions, empty without any.
+    pub busiest_weekday: String,
+    /// `YYYY-MM` of the month with the most contributions, empty without any.
+    pub busiest_month: String,
+    /// Most days in a row without contributions.
+    pub longest_gap: u32,
+    /// The newest 7 days against the 7 before them.
+    pub week_over_week: Change,
+    /// The newest 30 days against the 30 before them.
+    pub month_over_month: Change,
+}
+
+impl Insights {
+    /// The busiest month as e.g. `Mar 2025`.
+    pub fn busiest_month_label(&self) -> String {
+        NaiveDate::parse_from_str(&format!("{}-01", self.busiest_month), "%Y-%m-%d")
+            .map(|month| month.format("%b %Y").to_string())
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+pub struct Change {
+    pub current: u32,
+    pub previous: u32,
+}
+
+impl Change {
+    /// Relative change in percent, `None` when there's nothing to compare against.
+    pub fn percent(&self) -> Option<f32> {
+        if self.previous == 0 {
+            return None;
+        }
+        Some(round((self.current as f32 - self.previous as f32) / self.previous as f32 * 100.0))
+    }
+
+    /// The current count with its change, e.g. `12 (+20%)`.
+    pub fn label(&self) -> String {
+        match self.percent() {
+            Some(percent) => format!("{} ({:+.0}%)", self.current, percent),
+            None => self.current.to_string(),
+        }
+    }
+}
+
+fn round(value: f32) -> f32 {
+    (value * 100.0).round() / 100.0
+}
+
+/// Compares the sum of the newest `window` days with the `window` days before them.
+fn change(counts: &[u32], window: usize) -> Change {
+    let mut windows = counts.rchunks(window);
+    let current = windows.next().map(|days| days.iter().sum()).unwrap_or(0);
+    let previous = windows.next().filter(|days| days.len() == window).map(|days| days.iter().sum()).unwrap_or(0);
+    Change { current, previous }
+}
+
+/// Computes the insights of `days`, sorted oldest first.
+pub fn insights(days: &[(String, u32, String)]) -> Insights {
+    let counts: Vec<u32> = days.iter().map(|(_, c, _)| *c).collect();
+    let mut active: Vec<u32> = counts.iter().copied().filter(|c| *c > 0).collect();
+    active.sort();
+    let total: u32 = active.iter().sum();
+    let n = active.len();
+    let median = match n {
+        0 => 0.0,
+        n if n % 2 == 0 => (active[n / 2 - 1] + active[n / 2]) as f32 / 2.0,
+        n => active[n / 2] as f32,
+    };
+
+    let mut weekday_totals = [0; 7];
+    let mut month_totals: BTreeMap<String, u32> = BTreeMap::new();
+    for (date, count, _) in days {
+        if let Ok(date) = NaiveDate::parse_from_str(date, "%Y-%m-%d") {
+            weekday_totals[date.weekday().num_days_from_sunday() as usize] += count;
+            *month_totals.entry(date.format("%Y-%m").to_string()).or_default() += count;
+        }
+    }
+    // Ties go to the earlier weekday or month
+    let busiest_weekday = weekday_totals.iter().enumerate()
+        .filter(|(_, total)| **total > 0)
+        .fold(None, |best: Option<(usize, u32)>, (i, total)| match best {
+            Some((_, best_total)) if best_total >= *total => best,
+            _ => Some((i, *total)),
+        })
+        .map(|(i, _)| WEEKDAYS[i].to_string())
+        .unwrap_or_default();
+    let busiest_month = month_totals.iter()
+        .filter(|(_, total)| **total > 0)
+        .fold(None, |best: Option<(&String, u32)>, (month, total)| match best {
+            Some((_, best_total)) if best_total >= *total => best,
+            _ => Some((month, *total)),
+        })
+        .map(|(month, _)| month.clone())
+        .unwrap_or_default();
+
+    let mut longest_gap = 0;
+    let mut gap = 0;
+    for count in &counts {
+        gap = if *count == 0 { gap + 1 } else { 0 };
+        longest_gap = longest_gap.max(gap);
+    }
+
+    Insights {
+        active_days: n as u32,
+        active_day_percentage: if counts.is_empty() { 0.0 } else { round(n as f32 / counts.len() as f32 * 100.0) },
+        mean_per_active_day: if n == 0 { 0.0 } else { round(total as f32 / n as f32) },
+        median_per_active_day: median,
+        weekday_totals,
+        busiest_weekday,
+        busiest_month,
+        longest_gap,
+        week_over_week: change(&counts, 7),
+        month_over_month: change(&counts, 30),
+    }
+}
@@ -0,0 +1,119 @@
+use serde::Serialize;
+use crate::{ContributionStats, HighScore, ParseWarning};
+use crate::insights::Change;
+use crate::levels;
+
+/// Version of the `/api/v1/` response. Bumped along with the path whenever a field is
+/// removed, renamed or changes meaning; adding fields keeps the version.
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize)]
+pub struct StatsResponse<'a> {
+    pub schema_version: u32,
+    pub username: &'a str,
+    pub today: u32,
+    pub current_streak: u32,
+    pub longest_streak: u32,
+    /// Date the current streak started on, `null` without one.
+    pub current_streak_start: Option<&'a str>,
+    /// One of `strict`, `grace`, `skip-N` or `weekdays`.
+    pub streak_policy: String,
+    pub streak_policy_description: String,
+    pub high_score: &'a HighScore,
+    pub quartiles: [u32; 5],
+    pub yearly_contributions: u32,
+    pub active_days: u32,
+    pub active_day_percentage: f32,
+    pub mean_per_active_day: f32,
+    pub median_per_active_day: f32,
+    /// Contributions per weekday, Sunday first.
+    pub weekday_totals: [u32; 7],
+    pub busiest_weekday: Option<&'a str>,
+    /// `YYYY-MM`
+    pub busiest_month: Option<&'a str>,
+    pub longest_gap: u32,
+    pub week_over_week: ChangeResponse,
+    pub month_over_month: ChangeResponse,
+    /// Number of levels days are bucketed into, including the empty level 0.
+    pub levels: usize,
+    /// A day is at least level `i + 1` when its count is above `thresholds[i]`.
+    pub thresholds: &'a [u32],
+    pub daily_contributions: Vec<DailyContribution<'a>>,
+    pub warnings: &'a [ParseWarning],
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChangeResponse {
+    pub current: u32,
+    pub previous: u32,
+    /// `null` when `previous` is 0.
+    pub percent: Option<f32>,
+}
+
+impl From<Change> for ChangeResponse {
+    fn from(change: Change) -> Self {
+        Self { current: change.current, previous: change.previous, percent: change.percent() }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct DailyContribution<'a> {
+    pub date: &'a str,
+    pub count: u32,
+    pub level: usize,
+    pub label: &'a str,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ErrorResponse {
+    pub schema_version: u32,
+    pub error: String,
+}
+
+impl ErrorResponse {
+    pub fn new(error: impl Into<String>) -> Self {
+        Self { schema_version: SCHEMA_VERSION, error: error.into() }
+    }
+}
+
+fn non_empty(value: &str) -> Option<&str> {
+    Some(value).filter(|value| !value.is_empty())
+}
+
+pub fn stats_response<'a>(stats: &'a ContributionStats, level_count: usize, thresholds: &'a [u32]) -> StatsResponse<'a> {
+    let insights = &stats.insights;
+    StatsResponse {
+        schema_version: SCHEMA_VERSION,
+        username: &stats.username,
+        today: stats.today,
+        current_streak: stats.current_streak,
+        longest_streak: stats.longest_streak,
+        current_streak_start: non_empty(&stats.current_streak_start),
+        streak_policy: stats.streak_policy.to_string(),
+        streak_policy_description: stats.streak_policy.description(),
+        high_score: &stats.high_score,
+        quartiles: stats.quartiles,
+        yearly_contributions: stats.yearly_contributions,
+        active_days: insights.active_days,
+        active_day_percentage: insights.active_day_percentage,
+        mean_per_active_day: insights.mean_per_active_day,
+        median_per_active_day: insights.median_per_active_day,
+        weekday_totals: insights.weekday_totals,
+        busiest_weekday: non_empty(&insights.busiest_weekday),
+        busiest_month: non_empty(&insights.busiest_month),
+        longest_gap: insights.longest_gap,
+        week_over_week: insights.week_over_week.into(),
+        month_over_month: insights.month_over_month.into(),
+        levels: level_count,
+        thresholds,
+        daily_contributions: stats.daily_contributions.iter()
+            .map(|(date, count, label)| DailyContribution {
+                date,
+                count: *count,
+                level: levels::level(*count, thresholds),
+                label,
+            })
+            .collect(),
+        warnings: &stats.warnings,
+    }
+}
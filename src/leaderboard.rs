@@ -0,0 +1,95 @@
+use crate::{Account, ContributionStats};
+use crate::templates::LeaderboardEntry;
+
+/// What users are ranked by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LeaderboardMetric {
+    Total,
+    Streak,
+    Longest,
+    Month,
+}
+
+impl LeaderboardMetric {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "total" => Some(LeaderboardMetric::Total),
+            "streak" => Some(LeaderboardMetric::Streak),
+            "longest" => Some(LeaderboardMetric::Longest),
+            "month" => Some(LeaderboardMetric::Month),
+            _ => None,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            LeaderboardMetric::Total => "Contributions this year",
+            LeaderboardMetric::Streak => "Current streak",
+            LeaderboardMetric::Longest => "Longest streak",
+            LeaderboardMetric::Month => "Contributions in the last 30 days",
+        }
+    }
+
+    pub fn value(&self, stats: &ContributionStats) -> u32 {
+        match self {
+            LeaderboardMetric::Total => stats.daily_contributions.iter().map(|(_, c, _)| c).sum(),
+            LeaderboardMetric::Streak => stats.current_streak,
+            LeaderboardMetric::Longest => stats.longest_streak,
+            LeaderboardMetric::Month => stats.daily_contributions.iter().rev().take(30).map(|(_, c, _)| c).sum(),
+        }
+    }
+}
+
+/// Ranks users by `metric`, highest first. Users with equal values share a rank,
+/// and users whose stats couldn't be fetched are listed last with their error.
+pub fn rank(
+    accounts: &[(Account, String)],
+    results: &[Result<ContributionStats, String>],
+    metric: LeaderboardMetric,
+) -> Vec<LeaderboardEntry> {
+    let mut ranked = Vec::new();
+    let mut failed = Vec::new();
+    for ((account, base_url), result) in accounts.iter().zip(results) {
+        let url = format!("{}/{}", base_url, account.username);
+        match result {
+            Ok(stats) => ranked.push((account.to_string(), url, metric.value(stats))),
+            Err(e) => failed.push(LeaderboardEntry {
+                rank: None,
+                username: account.to_string(),
+                url,
+                value: String::new(),
+                error: Some(e.clone()),
+            }),
+        }
+    }
+    ranked.sort_by_key(|(_, _, value)| std::cmp::Reverse(*value));
+    let mut entries = Vec::new();
+    let mut previous = None;
+    for (i, (username, url, value)) in ranked.into_iter().enumerate() {
+        let rank = match previous {
+            Some((previous_value, previous_rank)) if previous_value == value => previous_rank,
+            _ => i + 1,
+        };
+        previous = Some((value, rank));
+        entries.push(LeaderboardEntry {
+            rank: Some(rank),
+            username,
+            url,
+            value: value.to_string(),
+            error: None,
+        });
+    }
+    entries.extend(failed);
+    entries
+}
+
+/// An entry for a user that couldn't be resolved to an account, listed with its error.
+pub fn unresolved(user: &str, error: String) -> LeaderboardEntry {
+    LeaderboardEntry {
+        rank: None,
+        username: user.to_string(),
+        url: String::new(),
+        value: String::new(),
+        error: Some(error),
+    }
+}
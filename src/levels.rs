@@ -0,0 +1,209 @@
+use crate::ContributionStats;
+
+/// The cutoffs used before scales were configurable: >0, >4, >8 and >15 contributions.
+pub const FIXED_THRESHOLDS: [u32; 4] = [0, 4, 8, 15];
+/// Number of levels the fixed cutoffs are defined for.
+pub const FIXED_LEVELS: usize = FIXED_THRESHOLDS.len() + 1;
+pub const MIN_LEVELS: usize = 3;
+pub const MAX_LEVELS: usize = 10;
+
+/// How the count of each day is mapped to one of the color levels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scale {
+    /// Always the same cutoffs, regardless of the user.
+    Fixed,
+    /// Quantiles of the user's active days, the way GitHub buckets its own calendar.
+    Quartile,
+    /// Evenly spaced between zero and the user's busiest day.
+    Linear,
+    /// Evenly spaced on a log scale, so a few outliers don't flatten every other day.
+    Log,
+}
+
+impl Scale {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "fixed" => Some(Scale::Fixed),
+            "quartile" => Some(Scale::Quartile),
+            "linear" => Some(Scale::Linear),
+            "log" => Some(Scale::Log),
+            _ => None,
+        }
+    }
+}
+
+/// How the level of each day is drawn, on top of its fill color.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    /// Fill color only.
+    Color,
+    /// Busier days get larger squares.
+    Size,
+    /// Busier days get denser dots.
+    Dots,
+    /// Busier days get denser diagonal lines.
+    Hatch,
+}
+
+impl Encoding {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "color" => Some(Encoding::Color),
+            "size" => Some(Encoding::Size),
+            "dots" => Some(Encoding::Dots),
+            "hatch" => Some(Encoding::Hatch),
+            _ => None,
+        }
+    }
+
+    pub fn is_pattern(&self) -> bool {
+        matches!(self, Encoding::Dots | Encoding::Hatch)
+    }
+}
+
+/// Cutoffs for `levels` color levels: a day lands in level `i + 1` when its count
+/// is above `thresholds[i]`, and in level 0 when it has no contributions.
+/// The fixed scale only has cutoffs for [`FIXED_LEVELS`] levels.
+pub fn thresholds(scale: Scale, stats: &ContributionStats, levels: usize) -> Vec<u32> {
+    let steps = levels.saturating_sub(1).max(1);
+    let max_count = stats.daily_contributions.iter().map(|(_, c, _)| *c).max().unwrap_or(0);
+    match scale {
+        Scale::Fixed => FIXED_THRESHOLDS.iter().copied().take(steps).collect(),
+        Scale::Quartile => {
+            let mut active: Vec<u32> = stats.daily_contributions.iter()
+                .map(|(_, c, _)| *c)
+                .filter(|c| *c > 0)
+                .collect();
+            active.sort();
+            (0..steps)
+                .map(|i| if i == 0 || active.is_empty() { 0 } else { active[(active.len() - 1) * i / steps] })
+                .collect()
+        },
+        Scale::Linear => (0..steps)
+            .map(|i| (max_count as f64 * i as f64 / steps as f64).floor() as u32)
+            .collect(),
+        Scale::Log => (0..steps)
+            .map(|i| ((max_count as f64 + 1.0).powf(i as f64 / steps as f64) - 1.0).floor() as u32)
+            .collect(),
+    }
+}
+
+/// Parses explicit cutoffs such as `0,4,8,15`, which must be ascending and one fewer than `levels`.
+pub fn parse_thresholds(value: &str, levels: usize) -> Result<Vec<u32>, String> {
+    let thresholds = value.split(',')
+        .map(|v| v.trim().parse::<u32>().map_err(|_| format!("Invalid threshold '{}'", v)))
+        .collect::<Result<Vec<_>, _>>()?;
+    if thresholds.len() != levels - 1 {
+        return Err(format!("Expected {} thresholds, got {}", levels - 1, thresholds.len()));
+    }
+    if thresholds.windows(2).any(|pair| pair[0] > pair[1]) {
+        return Err("Thresholds must be in ascending order".to_string());
+    }
+    Ok(thresholds)
+}
+
+pub fn parse_levels(value: &str) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(levels) if (MIN_LEVELS..=MAX_LEVELS).contains(&levels) => Ok(levels),
+        _ => Err(format!("levels must be a number from {} to {}", MIN_LEVELS, MAX_LEVELS)),
+    }
+}
+
+/// The color level of a day with `count` contributions.
+pub fn level(count: u32, thresholds: &[u32]) -> usize {
+    if count == 0 {
+        return 0;
+    }
+    thresholds.iter().filter(|t| count > **t).count().max(1)
+}
+
+/// Describes the counts that land in `level`, e.g. `5-8 contributions`,
+/// or `None` when the thresholds leave the level empty.
+pub fn range_label(level: usize, thresholds: &[u32]) -> Option<String> {
+    if level == 0 {
+        return Some("no contributions".to_string());
+    }
+    // Nothing is above a threshold of u32::MAX, so the level above it stays empty
+    let lower = if level == 1 { 1 } else { thresholds[level - 1].checked_add(1)? };
+    match thresholds.get(level) {
+        Some(&upper) if upper < lower => None,
+        Some(&1) => Some("1 contribution".to_string()),
+        Some(&upper) if upper == lower => Some(format!("{} contributions", upper)),
+        Some(&upper) => Some(format!("{}-{} contributions", lower, upper)),
+        None => Some(format!("{} or more contributions", lower)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::stats_from;
+
+    fn stats(counts: &[u32]) -> ContributionStats {
+        stats_from("2024-01-01", counts)
+    }
+
+    #[test]
+    fn parse_thresholds_checks_count_and_order() {
+        assert_eq!(parse_thresholds("0, 4,8,15", 5), Ok(vec![0, 4, 8, 15]));
+        assert_eq!(parse_thresholds("0,4,4,15", 5), Ok(vec![0, 4, 4, 15]));
+        assert!(parse_thresholds("0,4,8", 5).is_err());
+        assert!(parse_thresholds("0,8,4,15", 5).is_err());
+        assert!(parse_thresholds("0,4,x,15", 5).is_err());
+        assert!(parse_thresholds("0,-1,8,15", 5).is_err());
+    }
+
+    #[test]
+    fn level_buckets_above_each_threshold() {
+        let thresholds = FIXED_THRESHOLDS;
+        assert_eq!(level(0, &thresholds), 0);
+        assert_eq!(level(1, &thresholds), 1);
+        assert_eq!(level(4, &thresholds), 1);
+        assert_eq!(level(5, &thresholds), 2);
+        assert_eq!(level(15, &thresholds), 3);
+        assert_eq!(level(16, &thresholds), 4);
+        assert_eq!(level(u32::MAX, &thresholds), 4);
+    }
+
+    #[test]
+    fn scales_produce_one_threshold_fewer_than_levels() {
+        let stats = stats(&[0, 1, 2, 3, 4, 5, 6, 7, 8, 100]);
+        assert_eq!(thresholds(Scale::Fixed, &stats, 5), vec![0, 4, 8, 15]);
+        assert_eq!(thresholds(Scale::Fixed, &stats, 3), vec![0, 4]);
+        assert_eq!(thresholds(Scale::Linear, &stats, 5), vec![0, 25, 50, 75]);
+        assert_eq!(thresholds(Scale::Quartile, &stats, 5), vec![0, 3, 5, 7]);
+        let log = thresholds(Scale::Log, &stats, 5);
+        assert_eq!(log.len(), 4);
+        assert_eq!(log[0], 0);
+        assert!(log.windows(2).all(|pair| pair[0] <= pair[1]));
+    }
+
+    #[test]
+    fn range_label_describes_each_level() {
+        let thresholds = FIXED_THRESHOLDS;
+        assert_eq!(range_label(0, &thresholds).as_deref(), Some("no contributions"));
+        assert_eq!(range_label(1, &thresholds).as_deref(), Some("1-4 contributions"));
+        assert_eq!(range_label(2, &thresholds).as_deref(), Some("5-8 contributions"));
+        assert_eq!(range_label(4, &thresholds).as_deref(), Some("16 or more contributions"));
+        assert_eq!(range_label(1, &[0, 1, 1]).as_deref(), Some("1 contribution"));
+        assert_eq!(range_label(2, &[0, 1, 1]), None);
+        assert_eq!(range_label(2, &[0, 1, 2]).as_deref(), Some("2 contributions"));
+    }
+
+    #[test]
+    fn range_label_does_not_overflow_on_max_thresholds() {
+        let thresholds = parse_thresholds("0,4294967295,4294967295,4294967295", 5).unwrap();
+        assert_eq!(range_label(1, &thresholds).as_deref(), Some("1-4294967295 contributions"));
+        for level in 2..5 {
+            assert_eq!(range_label(level, &thresholds), None);
+        }
+    }
+
+    #[test]
+    fn scales_handle_a_calendar_without_contributions() {
+        let stats = stats(&[0, 0, 0]);
+        for scale in [Scale::Quartile, Scale::Linear, Scale::Log] {
+            assert_eq!(thresholds(scale, &stats, 4), vec![0, 0, 0]);
+        }
+    }
+}
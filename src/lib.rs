@@ -1,13 +1,31 @@
 use serde::Deserialize;
 use anyhow::{Result, anyhow};
-use scraper::{Html, Selector};
-use std::collections::HashMap;
-use log::{info, error};
+use log::{error, warn};
+use chrono::{Datelike, NaiveDate};
+use std::collections::BTreeMap;
 
 pub mod api;
 pub mod color;
 pub mod config;
+pub mod contrast;
+pub mod export;
+pub mod insights;
+pub mod json_api;
+pub mod leaderboard;
+pub mod levels;
+pub mod metrics;
+pub mod raster;
+pub mod sources;
+pub mod streaks;
 pub mod templates;
+pub mod theme;
+pub mod tiles;
+#[cfg(test)]
+mod test_support;
+
+pub use insights::Insights;
+pub use sources::{Account, DateRange, ParseWarning};
+pub use streaks::StreakPolicy;
 
 #[derive(Debug, Deserialize, serde::Serialize, Clone)]
 pub struct ContributionStats {
@@ -15,10 +33,21 @@ pub struct ContributionStats {
     pub today: u32,
     pub current_streak: u32,
     pub longest_streak: u32,
+    /// Date the current streak started on, empty without one.
+    #[serde(default)]
+    pub current_streak_start: String,
     pub high_score: HighScore,
     pub quartiles: [u32; 5],
     pub daily_contributions: Vec<(String, u32, String)>, // (date, count, label)
-    pub yearly_contributions: String,
+    pub yearly_contributions: u32,
+    /// Which empty days `current_streak` and `longest_streak` forgive.
+    #[serde(default)]
+    pub streak_policy: StreakPolicy,
+    #[serde(default)]
+    pub insights: Insights,
+    /// Problems found reading the forge's calendar.
+    #[serde(default)]
+    pub warnings: Vec<ParseWarning>,
 }
 
 #[derive(Debug, Deserialize, serde::Serialize, Clone)]
@@ -27,124 +56,240 @@ pub struct HighScore {
     pub date: String,
 }
 
-pub async fn fetch_contribution_stats(username: &str, _github_url: Option<&str>) -> Result<ContributionStats> {
-    let url = format!("https://github.com/users/{}/contributions", username);
-    info!("Fetching contributions for user '{}' from {}", username, url);
-    let body = match reqwest::get(&url).await {
-        Ok(resp) => {
-            info!("Successfully fetched page for user '{}'.", username);
-            match resp.text().await {
-                Ok(text) => text,
-                Err(e) => {
-                    error!("Failed to read response text for user '{}': {}", username, e);
-                    return Err(anyhow!("Failed to read response text: {}", e));
-                }
-            }
-        },
-        Err(e) => {
-            error!("Failed to fetch page for user '{}': {}", username, e);
-            return Err(anyhow!("Failed to fetch page: {}", e));
-        }
-    };
-    let document = Html::parse_document(&body);
-
-    // Build a map from td id to tooltip text
-    let tooltip_selector = Selector::parse("tool-tip").unwrap();
-    let mut tooltip_map = HashMap::new();
-    for tooltip in document.select(&tooltip_selector) {
-        if let Some(for_id) = tooltip.value().attr("for") {
-            let text = tooltip.text().collect::<String>().trim().to_string();
-            tooltip_map.insert(for_id.to_string(), text);
-        }
+/// Fetches the calendar for `account`, over `range` or the past year, and derives its statistics.
+pub async fn fetch_contribution_stats(account: &Account, base_url: &str, github_token: Option<&str>, range: Option<DateRange>) -> Result<ContributionStats> {
+    let calendar = sources::fetch_calendar(account, base_url, github_token, range).await?;
+    if calendar.days.is_empty() {
+        error!("No contributions found for user {}", account);
+        return Err(anyhow!("No contributions found for user {}", account));
     }
-
-    // Parse yearly contributions as text from the h2 element
-    let mut yearly_contributions = String::new();
-    if let Ok(h2_selector) = Selector::parse("h2#js-contribution-activity-description") {
-        if let Some(h2) = document.select(&h2_selector).next() {
-            let text = h2.text().collect::<String>().trim().to_string();
-            if let Some(num) = text.split_whitespace().next() {
-                yearly_contributions = num.to_string();
-            }
-        }
+    let mut warnings = calendar.warnings;
+    let computed = calendar.days.iter().map(|(_, c, _)| c).sum();
+    if let Some(reported) = calendar.yearly_contributions.filter(|reported| *reported != computed) {
+        warnings.push(ParseWarning::TotalMismatch { reported, computed });
     }
+    for warning in &warnings {
+        warn!("Calendar of {}: {}", account, warning);
+    }
+    let mut stats = compute_stats(&account.to_string(), calendar.days, computed, StreakPolicy::default());
+    stats.warnings = warnings;
+    Ok(stats)
+}
 
-    let td_selector = Selector::parse("td.ContributionCalendar-day").unwrap();
-    let mut contributions: Vec<(String, u32, String)> = Vec::new();
+/// Computes the summary statistics over a series of daily contributions.
+pub fn compute_stats(username: &str, mut contributions: Vec<(String, u32, String)>, yearly_contributions: u32, streak_policy: StreakPolicy) -> ContributionStats {
+    // Sort by date string (alphabetically, which works for YYYY-MM-DD)
+    contributions.sort_by(|a, b| a.0.cmp(&b.0));
     let mut high_score = 0;
     let mut high_score_date = String::new();
-    for td in document.select(&td_selector) {
-        let date = td.value().attr("data-date").unwrap_or("").to_string();
-        let id = td.value().attr("id").unwrap_or("");
-        let tooltip_text = tooltip_map.get(id);
-        let count = tooltip_text
-            .and_then(|text| parse_contribution_count(text))
-            .unwrap_or(0);
-        let label = tooltip_text.cloned().unwrap_or_default();
-        if count > high_score {
-            high_score = count;
+    for (date, count, _) in &contributions {
+        if *count > high_score {
+            high_score = *count;
             high_score_date = date.clone();
         }
-        if !date.is_empty() {
-            contributions.push((date, count, label));
-        }
-    }
-    if contributions.is_empty() {
-        error!("No contributions found for user {}", username);
-        return Err(anyhow!("No contributions found for user {}", username));
     }
-    // Sort by date string (alphabetically, which works for YYYY-MM-DD)
-    contributions.sort_by(|a, b| a.0.cmp(&b.0));
     let counts: Vec<u32> = contributions.iter().map(|(_, c, _)| *c).collect();
     // Calculate quartiles
     let mut sorted = counts.clone();
     sorted.sort();
     let n = sorted.len();
     let quartiles = [
-        *sorted.get(0).unwrap_or(&0),
+        *sorted.first().unwrap_or(&0),
         *sorted.get(n / 4).unwrap_or(&0),
         *sorted.get(n / 2).unwrap_or(&0),
         *sorted.get(3 * n / 4).unwrap_or(&0),
         *sorted.last().unwrap_or(&0),
     ];
-    // Calculate streaks
-    let mut current_streak = 0;
-    let mut longest_streak = 0;
-    let mut streak = 0;
-    for &count in counts.iter().rev() {
-        if count > 0 {
-            streak += 1;
-            if streak > longest_streak {
-                longest_streak = streak;
-            }
-        } else {
-            if current_streak == 0 {
-                current_streak = streak;
-            }
-            streak = 0;
-        }
-    }
-    if current_streak == 0 {
-        current_streak = streak;
-    }
+    // Without knowing today, grace can't tell an unfinished day apart; apply_streak_policy can
+    let (current_streak, longest_streak, current_streak_start) = streaks::streaks(&contributions, streak_policy, None);
+    let insights = insights::insights(&contributions);
     let today = *counts.last().unwrap_or(&0);
-    Ok(ContributionStats {
+    ContributionStats {
         username: username.to_string(),
         today,
         current_streak,
         longest_streak,
+        current_streak_start,
         high_score: HighScore { score: high_score, date: high_score_date },
         quartiles,
         daily_contributions: contributions,
         yearly_contributions,
-    })
+        streak_policy,
+        insights,
+        warnings: Vec::new(),
+    }
+}
+
+/// Sums several users' contributions per day into one series, labelling each
+/// day with the per-user breakdown.
+pub fn merge_stats(all_stats: &[ContributionStats]) -> ContributionStats {
+    let mut by_date: BTreeMap<NaiveDate, Vec<(&str, u32)>> = BTreeMap::new();
+    for stats in all_stats {
+        for (date, count, _) in &stats.daily_contributions {
+            if let Ok(date) = NaiveDate::parse_from_str(date, "%Y-%m-%d") {
+                by_date.entry(date).or_default().push((&stats.username, *count));
+            }
+        }
+    }
+    let mut contributions = Vec::new();
+    if let (Some(first), Some(last)) = (by_date.keys().next(), by_date.keys().next_back()) {
+        // Start on a Sunday and leave no gaps so the days still line up into weeks
+        let start = *first - chrono::Duration::days(first.weekday().num_days_from_sunday() as i64);
+        for date in start.iter_days().take_while(|date| date <= last) {
+            let parts = by_date.get(&date).map(Vec::as_slice).unwrap_or_default();
+            let count = parts.iter().map(|(_, c)| c).sum();
+            let mut label = contribution_label(date, count);
+            if count > 0 {
+                let breakdown = parts.iter()
+                    .filter(|(_, c)| *c > 0)
+                    .map(|(username, c)| format!("{}: {}", username, c))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                label = format!("{} ({})", label, breakdown);
+            }
+            contributions.push((date.format("%Y-%m-%d").to_string(), count, label));
+        }
+    }
+    let yearly_contributions = contributions.iter().map(|(_, c, _)| c).sum();
+    let username = all_stats.iter().map(|s| s.username.as_str()).collect::<Vec<_>>().join(" + ");
+    let streak_policy = all_stats.first().map(|s| s.streak_policy).unwrap_or_default();
+    compute_stats(&username, contributions, yearly_contributions, streak_policy)
 }
 
-fn parse_contribution_count(text: &str) -> Option<u32> {
-    // Examples: "No contributions on July 14th.", "7 contributions on September 1st.", "1 contribution on November 3rd."
-    if text.starts_with("No contributions") {
-        Some(0)
-    } else {
-        text.split_whitespace().next()?.parse::<u32>().ok()
+/// Re-anchors `stats` on the viewer's `today`, since forges end their calendar on
+/// their own date. Days past `today` are folded into `today`, since the viewer made
+/// those contributions before their own midnight, and days the forge hasn't reached
+/// yet are added empty. A `range` that ends before `today` is left as asked for,
+/// as are calendars that end more than a couple of days from `today`.
+pub fn localize_stats(stats: ContributionStats, today: NaiveDate, range: Option<DateRange>) -> ContributionStats {
+    if range.is_some_and(|range| range.to < today) {
+        return stats;
+    }
+    let last = stats.daily_contributions.last()
+        .and_then(|(date, _, _)| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok());
+    let Some(last) = last.filter(|last| (today - *last).num_days().abs() <= 2) else {
+        return stats;
+    };
+    if last == today {
+        return stats;
+    }
+    let today_string = today.format("%Y-%m-%d").to_string();
+    let mut contributions = stats.daily_contributions;
+    let ahead = contributions.iter().position(|(date, _, _)| *date > today_string).unwrap_or(contributions.len());
+    let carried: u32 = contributions.drain(ahead..).map(|(_, c, _)| c).sum();
+    if carried > 0 {
+        match contributions.last_mut() {
+            Some(day) if day.0 == today_string => {
+                day.1 += carried;
+                day.2 = contribution_label(today, day.1);
+            },
+            _ => contributions.push((today_string, carried, contribution_label(today, carried))),
+        }
+    }
+    for date in last.iter_days().skip(1).take_while(|date| *date <= today) {
+        contributions.push((date.format("%Y-%m-%d").to_string(), 0, contribution_label(date, 0)));
+    }
+    let total = contributions.iter().map(|(_, c, _)| c).sum();
+    let mut localized = compute_stats(&stats.username, contributions, total, stats.streak_policy);
+    localized.warnings = stats.warnings;
+    localized
+}
+
+/// Recounts the streaks of `stats` under `policy`, as seen on the viewer's `today`.
+pub fn apply_streak_policy(mut stats: ContributionStats, policy: StreakPolicy, today: NaiveDate) -> ContributionStats {
+    (stats.current_streak, stats.longest_streak, stats.current_streak_start) = streaks::streaks(&stats.daily_contributions, policy, Some(today));
+    stats.streak_policy = policy;
+    stats
+}
+
+/// Joins one user's calendars for consecutive ranges, e.g. several years, into one
+/// series so streaks carry over from one range into the next.
+pub fn combine_ranges(ranges: &[ContributionStats]) -> ContributionStats {
+    let mut by_date = BTreeMap::new();
+    for stats in ranges {
+        for day in &stats.daily_contributions {
+            by_date.insert(day.0.clone(), day.clone());
+        }
+    }
+    let contributions: Vec<_> = by_date.into_values().collect();
+    let total = contributions.iter().map(|(_, c, _)| c).sum();
+    let username = ranges.first().map(|s| s.username.as_str()).unwrap_or_default();
+    let streak_policy = ranges.first().map(|s| s.streak_policy).unwrap_or_default();
+    compute_stats(username, contributions, total, streak_policy)
+}
+
+/// Formats a tooltip the same way GitHub's contribution calendar does.
+pub fn contribution_label(date: NaiveDate, count: u32) -> String {
+    let day = date.day();
+    let suffix = match (day % 10, day % 100) {
+        (1, d) if d != 11 => "st",
+        (2, d) if d != 12 => "nd",
+        (3, d) if d != 13 => "rd",
+        _ => "th",
+    };
+    let on = format!("{} {}{}", date.format("%B"), day, suffix);
+    match count {
+        0 => format!("No contributions on {}.", on),
+        1 => format!("1 contribution on {}.", on),
+        n => format!("{} contributions on {}.", n, on),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{counts, date, stats};
+
+    #[test]
+    fn localize_folds_days_ahead_of_the_viewer_into_today() {
+        let localized = localize_stats(stats(&[("2024-01-08", 1), ("2024-01-09", 1), ("2024-01-10", 3)]), date("2024-01-09"), None);
+        assert_eq!(counts(&localized), vec![("2024-01-08", 1), ("2024-01-09", 4)]);
+        assert_eq!(localized.today, 4);
+        assert_eq!(localized.yearly_contributions, 5);
+        assert_eq!(localized.daily_contributions[1].2, "4 contributions on January 9th.");
+    }
+
+    #[test]
+    fn localize_keeps_today_when_nothing_is_folded() {
+        let localized = localize_stats(stats(&[("2024-01-08", 1), ("2024-01-09", 2), ("2024-01-10", 0)]), date("2024-01-09"), None);
+        assert_eq!(counts(&localized), vec![("2024-01-08", 1), ("2024-01-09", 2)]);
+        assert_eq!(localized.today, 2);
+        assert_eq!(localized.yearly_contributions, 3);
+    }
+
+    #[test]
+    fn localize_anchors_ranges_that_reach_today() {
+        let range = DateRange::new(date("2024-01-08"), date("2024-01-10")).unwrap();
+        let localized = localize_stats(stats(&[("2024-01-08", 1), ("2024-01-09", 2)]), date("2024-01-10"), Some(range));
+        assert_eq!(counts(&localized), vec![("2024-01-08", 1), ("2024-01-09", 2), ("2024-01-10", 0)]);
+    }
+
+    #[test]
+    fn localize_adds_days_the_forge_has_not_reached() {
+        let localized = localize_stats(stats(&[("2024-01-08", 1), ("2024-01-09", 2)]), date("2024-01-11"), None);
+        assert_eq!(counts(&localized), vec![("2024-01-08", 1), ("2024-01-09", 2), ("2024-01-10", 0), ("2024-01-11", 0)]);
+        assert_eq!(localized.today, 0);
+        assert_eq!(localized.yearly_contributions, 3);
+    }
+
+    #[test]
+    fn localize_leaves_past_ranges_and_matching_calendars_alone() {
+        let days = [("2023-12-30", 1), ("2023-12-31", 2)];
+        assert_eq!(counts(&localize_stats(stats(&days), date("2024-06-01"), None)), days.to_vec());
+        assert_eq!(counts(&localize_stats(stats(&days), date("2023-12-31"), None)), days.to_vec());
+        // Ranges that end a day or two before today aren't stretched up to it
+        for today in ["2024-01-01", "2024-01-02"] {
+            let range = DateRange::new(date("2023-12-30"), date("2023-12-31")).unwrap();
+            assert_eq!(counts(&localize_stats(stats(&days), date(today), Some(range))), days.to_vec());
+        }
+    }
+
+    #[test]
+    fn contribution_labels_match_github() {
+        assert_eq!(contribution_label(date("2024-07-14"), 0), "No contributions on July 14th.");
+        assert_eq!(contribution_label(date("2024-11-03"), 1), "1 contribution on November 3rd.");
+        assert_eq!(contribution_label(date("2024-09-01"), 7), "7 contributions on September 1st.");
+        assert_eq!(contribution_label(date("2024-09-11"), 2), "2 contributions on September 11th.");
+        assert_eq!(contribution_label(date("2024-09-22"), 2), "2 contributions on September 22nd.");
     }
 }
@@ -0,0 +1,127 @@
+use lazy_static::lazy_static;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use crate::ContributionStats;
+
+const PREFIX: &str = "glance_github_graph";
+/// Upper bounds of the upstream fetch latency histogram, in seconds.
+const FETCH_BUCKETS: [f64; 8] = [0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0];
+
+/// Name, help text and value of a per-user gauge.
+type UserGauge = (&'static str, &'static str, fn(&ContributionStats) -> u32);
+
+#[derive(Default)]
+struct Histogram {
+    buckets: [u64; FETCH_BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+#[derive(Default)]
+struct ServiceMetrics {
+    cache_hits: AtomicU64,
+    cache_misses: AtomicU64,
+    fetch_duration: Mutex<BTreeMap<String, Histogram>>,
+    fetch_failures: Mutex<BTreeMap<String, u64>>,
+    requests: Mutex<BTreeMap<(String, u16), u64>>,
+}
+
+lazy_static! {
+    static ref METRICS: ServiceMetrics = ServiceMetrics::default();
+}
+
+pub fn record_cache_hit() {
+    METRICS.cache_hits.fetch_add(1, Ordering::Relaxed);
+}
+
+pub fn record_cache_miss() {
+    METRICS.cache_misses.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Records how long fetching a calendar from `forge` took, and whether it failed.
+pub fn record_fetch(forge: &str, duration: Duration, failed: bool) {
+    let seconds = duration.as_secs_f64();
+    {
+        let mut histograms = METRICS.fetch_duration.lock().unwrap();
+        let histogram = histograms.entry(forge.to_string()).or_default();
+        for (bucket, bound) in histogram.buckets.iter_mut().zip(FETCH_BUCKETS) {
+            if seconds <= bound {
+                *bucket += 1;
+            }
+        }
+        histogram.sum += seconds;
+        histogram.count += 1;
+    }
+    if failed {
+        *METRICS.fetch_failures.lock().unwrap().entry(forge.to_string()).or_default() += 1;
+    }
+}
+
+/// Counts a served request by its route pattern, e.g. `/graph/{username}`.
+pub fn record_request(route: &str, status: u16) {
+    *METRICS.requests.lock().unwrap().entry((route.to_string(), status)).or_default() += 1;
+}
+
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {}_{} {}", PREFIX, name, help);
+    let _ = writeln!(out, "# TYPE {}_{} {}", PREFIX, name, kind);
+}
+
+/// Renders the Prometheus text exposition of every user's stats and the service counters.
+/// Users that failed to fetch only report `user_up 0`.
+pub fn render(users: &[(String, Result<ContributionStats, String>)]) -> String {
+    let mut out = String::new();
+
+    header(&mut out, "user_up", "gauge", "Whether the user's contributions could be fetched.");
+    for (user, stats) in users {
+        let _ = writeln!(out, "{}_user_up{{user=\"{}\"}} {}", PREFIX, escape_label(user), stats.is_ok() as u8);
+    }
+    let gauges: [UserGauge; 6] = [
+        ("user_today_contributions", "Contributions on the newest day.", |s| s.today),
+        ("user_current_streak_days", "Current streak of days with contributions.", |s| s.current_streak),
+        ("user_longest_streak_days", "Longest streak of days with contributions.", |s| s.longest_streak),
+        ("user_yearly_contributions", "Contributions over the whole calendar.", |s| s.yearly_contributions),
+        ("user_high_score_contributions", "Contributions on the busiest day.", |s| s.high_score.score),
+        ("user_parse_warnings", "Problems found reading the user's calendar, such as a total that doesn't match the days.", |s| s.warnings.len() as u32),
+    ];
+    for (name, help, value) in gauges {
+        header(&mut out, name, "gauge", help);
+        for (user, stats) in users {
+            if let Ok(stats) = stats {
+                let _ = writeln!(out, "{}_{}{{user=\"{}\"}} {}", PREFIX, name, escape_label(user), value(stats));
+            }
+        }
+    }
+
+    header(&mut out, "cache_hits_total", "counter", "Stats served from the cache.");
+    let _ = writeln!(out, "{}_cache_hits_total {}", PREFIX, METRICS.cache_hits.load(Ordering::Relaxed));
+    header(&mut out, "cache_misses_total", "counter", "Stats that had to be fetched upstream with the cache enabled.");
+    let _ = writeln!(out, "{}_cache_misses_total {}", PREFIX, METRICS.cache_misses.load(Ordering::Relaxed));
+
+    header(&mut out, "upstream_fetch_duration_seconds", "histogram", "Time taken to fetch a calendar from a forge.");
+    for (forge, histogram) in METRICS.fetch_duration.lock().unwrap().iter() {
+        for (bucket, bound) in histogram.buckets.iter().zip(FETCH_BUCKETS) {
+            let _ = writeln!(out, "{}_upstream_fetch_duration_seconds_bucket{{forge=\"{}\",le=\"{}\"}} {}", PREFIX, forge, bound, bucket);
+        }
+        let _ = writeln!(out, "{}_upstream_fetch_duration_seconds_bucket{{forge=\"{}\",le=\"+Inf\"}} {}", PREFIX, forge, histogram.count);
+        let _ = writeln!(out, "{}_upstream_fetch_duration_seconds_sum{{forge=\"{}\"}} {}", PREFIX, forge, histogram.sum);
+        let _ = writeln!(out, "{}_upstream_fetch_duration_seconds_count{{forge=\"{}\"}} {}", PREFIX, forge, histogram.count);
+    }
+    header(&mut out, "upstream_fetch_failures_total", "counter", "Calendars that couldn't be fetched or parsed.");
+    for (forge, failures) in METRICS.fetch_failures.lock().unwrap().iter() {
+        let _ = writeln!(out, "{}_upstream_fetch_failures_total{{forge=\"{}\"}} {}", PREFIX, forge, failures);
+    }
+
+    header(&mut out, "http_requests_total", "counter", "Requests served, by route and status.");
+    for ((route, status), count) in METRICS.requests.lock().unwrap().iter() {
+        let _ = writeln!(out, "{}_http_requests_total{{route=\"{}\",status=\"{}\"}} {}", PREFIX, escape_label(route), status, count);
+    }
+    out
+}
@@ -0,0 +1,90 @@
+use anyhow::{Result, anyhow};
+use lazy_static::lazy_static;
+use resvg::{tiny_skia, usvg};
+use std::sync::Arc;
+
+/// Font for the month and weekday labels, so bitmaps look the same on any host.
+const FONT: &[u8] = include_bytes!("../assets/DejaVuSans.ttf");
+const FONT_FAMILY: &str = "DejaVu Sans";
+/// Largest bitmap side we render, to keep a single request from eating the memory.
+pub const MAX_SIZE: u32 = 4096;
+
+lazy_static! {
+    static ref FONTS: Arc<usvg::fontdb::Database> = {
+        let mut fonts = usvg::fontdb::Database::new();
+        fonts.load_font_data(FONT.to_vec());
+        Arc::new(fonts)
+    };
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RasterFormat {
+    Png,
+    WebP,
+}
+
+impl RasterFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            RasterFormat::Png => "png",
+            RasterFormat::WebP => "webp",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            RasterFormat::Png => "image/png",
+            RasterFormat::WebP => "image/webp",
+        }
+    }
+}
+
+/// How large to render, relative to the size the SVG declares.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RasterSize {
+    /// Exactly this many pixels wide, keeping the aspect ratio.
+    Width(u32),
+    /// This many pixels per SVG pixel.
+    Scale(f32),
+}
+
+/// Rasterizes `svg` and encodes it as `format`. Only solid colors render,
+/// since CSS variables don't exist outside a browser.
+pub fn render(svg: &str, size: RasterSize, format: RasterFormat) -> Result<Vec<u8>> {
+    let options = usvg::Options {
+        font_family: FONT_FAMILY.to_string(),
+        fontdb: FONTS.clone(),
+        ..Default::default()
+    };
+    let tree = usvg::Tree::from_str(svg, &options).map_err(|e| anyhow!("Failed to parse SVG: {}", e))?;
+    let scale = match size {
+        RasterSize::Width(width) => width as f32 / tree.size().width(),
+        RasterSize::Scale(scale) => scale,
+    };
+    let width = (tree.size().width() * scale).round() as u32;
+    let height = (tree.size().height() * scale).round() as u32;
+    if width > MAX_SIZE || height > MAX_SIZE {
+        return Err(anyhow!("Image would be {}x{}, the limit is {}x{}", width, height, MAX_SIZE, MAX_SIZE));
+    }
+    let mut pixmap = tiny_skia::Pixmap::new(width, height)
+        .ok_or_else(|| anyhow!("Invalid image size {}x{}", width, height))?;
+    resvg::render(&tree, tiny_skia::Transform::from_scale(scale, scale), &mut pixmap.as_mut());
+
+    match format {
+        RasterFormat::Png => pixmap.encode_png().map_err(|e| anyhow!("Failed to encode PNG: {}", e)),
+        RasterFormat::WebP => {
+            // The pixmap is premultiplied, WebP expects straight alpha
+            let rgba: Vec<u8> = pixmap.pixels().iter()
+                .flat_map(|pixel| {
+                    let pixel = pixel.demultiply();
+                    [pixel.red(), pixel.green(), pixel.blue(), pixel.alpha()]
+                })
+                .collect();
+            let mut webp = Vec::new();
+            image_webp::WebPEncoder::new(&mut webp)
+                .encode(&rgba, width, height, image_webp::ColorType::Rgba8)
+                .map_err(|e| anyhow!("Failed to encode WebP: {}", e))?;
+            Ok(webp)
+        },
+    }
+}
@@ -0,0 +1,319 @@
+use anyhow::{Result, anyhow};
+use chrono::{Datelike, Duration, NaiveDate};
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use serde::{Deserialize, Serialize};
+
+pub mod gitea;
+pub mod github;
+pub mod gitlab;
+
+/// Daily contributions as reported by a forge, before any statistics are derived.
+#[derive(Debug, Clone)]
+pub struct ContributionCalendar {
+    pub days: Vec<(String, u32, String)>, // (date, count, label)
+    /// Total the forge reports, `None` if it couldn't be read.
+    pub yearly_contributions: Option<u32>,
+    pub warnings: Vec<ParseWarning>,
+}
+
+/// Something that looked off while reading a forge's calendar.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ParseWarning {
+    /// The reported total couldn't be read, so the sum of the days is used.
+    UnreadableTotal { text: String },
+    /// The reported total disagrees with the sum of the days, which is used instead.
+    TotalMismatch { reported: u32, computed: u32 },
+}
+
+impl fmt::Display for ParseWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseWarning::UnreadableTotal { text } => write!(f, "Couldn't read the total from '{}', showing the sum of the days", text),
+            ParseWarning::TotalMismatch { reported, computed } => write!(f, "The forge reports {} contributions but the days add up to {}, showing the sum of the days", reported, computed),
+        }
+    }
+}
+
+/// Characters localized headings group thousands with, e.g. `1,234`, `1.234` or `1 234`.
+const THOUSANDS_SEPARATORS: [char; 7] = [',', '.', '\'', ' ', '\u{a0}', '\u{2009}', '\u{202f}'];
+
+/// Reads a total such as `1,234 contributions in the last year`, including localized
+/// forms like `1.234 Beiträge` or `1 234 contributions`. A separator only joins digits
+/// when it's followed by exactly three of them, so `Im Jahr 2023 1.234 Beiträge`
+/// holds two numbers. A bare year is only taken when there's no other number.
+pub fn parse_total(text: &str) -> Option<u32> {
+    if text.starts_with("No contributions") {
+        return Some(0);
+    }
+    let numbers = numbers(text);
+    let is_year = |(digits, grouped): &(String, bool)| {
+        !grouped && digits.len() == 4 && (digits.starts_with("19") || digits.starts_with("20"))
+    };
+    let (digits, _) = numbers.iter().find(|number| !is_year(number)).or(numbers.first())?;
+    digits.parse().ok()
+}
+
+/// The numbers in `text` with their thousands separators removed, and whether they had any.
+fn numbers(text: &str) -> Vec<(String, bool)> {
+    let chars: Vec<char> = text.chars().collect();
+    let is_digit = |i: usize| chars.get(i).is_some_and(char::is_ascii_digit);
+    let mut numbers = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        if !is_digit(i) {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while is_digit(i) {
+            i += 1;
+        }
+        let mut digits: String = chars[start..i].iter().collect();
+        let mut grouped = false;
+        // Only a leading group of up to three digits can be followed by more groups
+        if digits.len() <= 3 {
+            while THOUSANDS_SEPARATORS.contains(&chars.get(i).copied().unwrap_or_default())
+                && (i + 1..=i + 3).all(is_digit)
+                && !is_digit(i + 4) {
+                digits.extend(&chars[i + 1..=i + 3]);
+                grouped = true;
+                i += 4;
+            }
+        }
+        numbers.push((digits, grouped));
+    }
+    numbers
+}
+
+/// Inclusive span of days to fetch, instead of the default rolling year.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DateRange {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+}
+
+impl DateRange {
+    /// Longest span a forge renders as one calendar.
+    pub const MAX_DAYS: i64 = 366;
+
+    pub fn new(from: NaiveDate, to: NaiveDate) -> Result<Self> {
+        if from > to {
+            return Err(anyhow!("from {} is after to {}", from, to));
+        }
+        if (to - from).num_days() >= Self::MAX_DAYS {
+            return Err(anyhow!("from {} to {} is longer than a year", from, to));
+        }
+        Ok(Self { from, to })
+    }
+
+    /// A calendar year, cut off at today for the current one.
+    pub fn year(year: i32) -> Result<Self> {
+        let from = NaiveDate::from_ymd_opt(year, 1, 1).ok_or_else(|| anyhow!("Invalid year {}", year))?;
+        let today = chrono::Utc::now().date_naive();
+        if from > today {
+            return Err(anyhow!("Year {} hasn't started yet", year));
+        }
+        let to = NaiveDate::from_ymd_opt(year, 12, 31).unwrap().min(today);
+        Ok(Self { from, to })
+    }
+}
+
+impl fmt::Display for DateRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}..{}", self.from, self.to)
+    }
+}
+
+/// A forge that can report a user's contributions per day, over the past year
+/// or over `range` when one is given.
+pub trait ContributionSource {
+    fn fetch(&self, username: &str, range: Option<DateRange>) -> impl Future<Output = Result<ContributionCalendar>> + Send;
+}
+
+/// Where GitHub calendars are read from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataSource {
+    /// The GraphQL API when a token is set, the contributions page otherwise.
+    Auto,
+    GraphQl,
+    Scrape,
+}
+
+impl DataSource {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "auto" => Some(DataSource::Auto),
+            "graphql" => Some(DataSource::GraphQl),
+            "scrape" => Some(DataSource::Scrape),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Forge {
+    GitHub,
+    GitLab,
+    Gitea,
+    Forgejo,
+    Codeberg,
+}
+
+impl Forge {
+    pub fn prefix(&self) -> &'static str {
+        match self {
+            Forge::GitHub => "github",
+            Forge::GitLab => "gitlab",
+            Forge::Gitea => "gitea",
+            Forge::Forgejo => "forgejo",
+            Forge::Codeberg => "codeberg",
+        }
+    }
+
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            Forge::GitHub => "GitHub",
+            Forge::GitLab => "GitLab",
+            Forge::Gitea => "Gitea",
+            Forge::Forgejo => "Forgejo",
+            Forge::Codeberg => "Codeberg",
+        }
+    }
+
+    fn from_prefix(prefix: &str) -> Option<Self> {
+        match prefix {
+            "github" => Some(Forge::GitHub),
+            "gitlab" => Some(Forge::GitLab),
+            "gitea" => Some(Forge::Gitea),
+            "forgejo" => Some(Forge::Forgejo),
+            "codeberg" => Some(Forge::Codeberg),
+            _ => None,
+        }
+    }
+}
+
+/// A user on a specific forge, written as `username` for GitHub or `forge:username` otherwise.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Account {
+    pub forge: Forge,
+    pub username: String,
+}
+
+impl Account {
+    pub fn parse(value: &str) -> Result<Self> {
+        let (forge, username) = match value.split_once(':') {
+            Some((prefix, username)) => {
+                let forge = Forge::from_prefix(prefix)
+                    .ok_or_else(|| anyhow!("Unknown forge '{}'", prefix))?;
+                (forge, username)
+            },
+            None => (Forge::GitHub, value),
+        };
+        if username.is_empty() {
+            return Err(anyhow!("Missing username in '{}'", value));
+        }
+        Ok(Self { forge, username: username.to_string() })
+    }
+}
+
+impl fmt::Display for Account {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.forge {
+            Forge::GitHub => write!(f, "{}", self.username),
+            forge => write!(f, "{}:{}", forge.prefix(), self.username),
+        }
+    }
+}
+
+/// Fetches the calendar for `account` from the forge hosted at `base_url`.
+pub async fn fetch_calendar(account: &Account, base_url: &str, github_token: Option<&str>, range: Option<DateRange>) -> Result<ContributionCalendar> {
+    let base_url = base_url.trim_end_matches('/').to_string();
+    match (account.forge, github_token) {
+        (Forge::GitHub, Some(token)) => {
+            github::GitHubGraphQl { base_url, token: token.to_string() }.fetch(&account.username, range).await
+        },
+        (Forge::GitHub, None) => github::GitHubScraper { base_url }.fetch(&account.username, range).await,
+        (Forge::GitLab, _) => gitlab::GitLab { base_url }.fetch(&account.username, range).await,
+        (Forge::Gitea | Forge::Forgejo | Forge::Codeberg, _) => {
+            gitea::Gitea { base_url }.fetch(&account.username, range).await
+        },
+    }
+}
+
+/// Expands sparse per-day counts into the days of `range`, or else into the
+/// same Sunday-aligned year GitHub renders. These forges only report the past
+/// year, so older ranges are refused rather than shown as empty.
+pub(crate) fn fill_calendar(counts: HashMap<NaiveDate, u32>, range: Option<DateRange>) -> Result<ContributionCalendar> {
+    let today = chrono::Utc::now().date_naive();
+    let year_ago = today - Duration::days(365);
+    let first = year_ago - Duration::days(year_ago.weekday().num_days_from_sunday() as i64);
+    let (start, end) = match range {
+        Some(range) if range.from < first => {
+            return Err(anyhow!("Only the past year of contributions is available, not {}", range));
+        },
+        Some(range) => (range.from, range.to.min(today)),
+        None => (first, today),
+    };
+    let mut days = Vec::new();
+    let mut yearly = 0;
+    for date in start.iter_days().take_while(|date| *date <= end) {
+        let count = counts.get(&date).copied().unwrap_or(0);
+        yearly += count;
+        days.push((date.format("%Y-%m-%d").to_string(), count, crate::contribution_label(date, count)));
+    }
+    Ok(ContributionCalendar { days, yearly_contributions: Some(yearly), warnings: Vec::new() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn data_source_rejects_unknown_values() {
+        assert_eq!(DataSource::parse("auto"), Some(DataSource::Auto));
+        assert_eq!(DataSource::parse("graphql"), Some(DataSource::GraphQl));
+        assert_eq!(DataSource::parse("scrape"), Some(DataSource::Scrape));
+        assert_eq!(DataSource::parse("graphq"), None);
+        assert_eq!(DataSource::parse("Scrape"), None);
+    }
+
+    #[test]
+    fn parse_total_reads_grouped_thousands() {
+        assert_eq!(parse_total("1,234 contributions in the last year"), Some(1234));
+        assert_eq!(parse_total("1,234,567 contributions in the last year"), Some(1234567));
+        assert_eq!(parse_total("1.234 Beiträge im letzten Jahr"), Some(1234));
+        assert_eq!(parse_total("1 234 contributions au cours de la dernière année"), Some(1234));
+        assert_eq!(parse_total("1\u{a0}234 contributions"), Some(1234));
+        assert_eq!(parse_total("1\u{202f}234 contributions"), Some(1234));
+        assert_eq!(parse_total("12'345 Beiträge"), Some(12345));
+        assert_eq!(parse_total("523 contributions in the last year"), Some(523));
+    }
+
+    #[test]
+    fn parse_total_does_not_join_separate_numbers() {
+        assert_eq!(parse_total("Im Jahr 2023 1.234 Beiträge"), Some(1234));
+        assert_eq!(parse_total("Im Jahr 2023 523 Beiträge"), Some(523));
+        assert_eq!(parse_total("1,234 contributions in 2023"), Some(1234));
+        assert_eq!(parse_total("523 contributions in 2023"), Some(523));
+        assert_eq!(parse_total("12 34 contributions"), Some(12));
+        assert_eq!(parse_total("1,2345 contributions"), Some(1));
+        assert_eq!(parse_total("1.5 contributions"), Some(1));
+    }
+
+    #[test]
+    fn parse_total_takes_a_year_like_count_when_it_is_the_only_number() {
+        assert_eq!(parse_total("2023 contributions in the last year"), Some(2023));
+        assert_eq!(parse_total("2,023 contributions in 2023"), Some(2023));
+    }
+
+    #[test]
+    fn parse_total_handles_missing_and_oversized_totals() {
+        assert_eq!(parse_total("No contributions in the last year"), Some(0));
+        assert_eq!(parse_total("contributions in the last year"), None);
+        assert_eq!(parse_total(""), None);
+        assert_eq!(parse_total("99,999,999,999 contributions"), None);
+    }
+}
@@ -0,0 +1,47 @@
+use serde::Deserialize;
+use anyhow::{Result, anyhow};
+use std::collections::HashMap;
+use log::{info, error};
+use super::{ContributionCalendar, ContributionSource, DateRange, fill_calendar};
+
+pub const CODEBERG_URL: &str = "https://codeberg.org";
+
+#[derive(Debug, Deserialize)]
+struct HeatmapEntry {
+    timestamp: i64,
+    contributions: u32,
+}
+
+/// Reads the `/api/v1/users/{username}/heatmap` endpoint shared by Gitea and Forgejo.
+pub struct Gitea {
+    pub base_url: String,
+}
+
+impl ContributionSource for Gitea {
+    async fn fetch(&self, username: &str, range: Option<DateRange>) -> Result<ContributionCalendar> {
+        let url = format!("{}/api/v1/users/{}/heatmap", self.base_url, username);
+        info!("Fetching contributions for user '{}' from {}", username, url);
+        let response = match reqwest::get(&url).await.and_then(|resp| resp.error_for_status()) {
+            Ok(resp) => resp,
+            Err(e) => {
+                error!("Failed to fetch heatmap for user '{}': {}", username, e);
+                return Err(anyhow!("Failed to fetch heatmap: {}", e));
+            }
+        };
+        let heatmap: Vec<HeatmapEntry> = match response.json().await {
+            Ok(json) => json,
+            Err(e) => {
+                error!("Failed to parse heatmap for user '{}': {}", username, e);
+                return Err(anyhow!("Failed to parse heatmap: {}", e));
+            }
+        };
+        // Entries are bucketed by timestamp rather than by day, so sum them per UTC date
+        let mut counts = HashMap::new();
+        for entry in heatmap {
+            if let Some(time) = chrono::DateTime::from_timestamp(entry.timestamp, 0) {
+                *counts.entry(time.date_naive()).or_insert(0) += entry.contributions;
+            }
+        }
+        fill_calendar(counts, range)
+    }
+}
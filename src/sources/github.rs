@@ -0,0 +1,302 @@
+use serde::Deserialize;
+use anyhow::{Result, anyhow};
+use scraper::{Html, Selector};
+use std::collections::HashMap;
+use log::{info, error};
+use super::{ContributionCalendar, ContributionSource, DateRange, ParseWarning, parse_total};
+
+pub const DEFAULT_GITHUB_URL: &str = "https://github.com";
+
+const CONTRIBUTION_CALENDAR_QUERY: &str = "
+query($login: String!, $from: DateTime, $to: DateTime) {
+  user(login: $login) {
+    contributionsCollection(from: $from, to: $to) {
+      contributionCalendar {
+        totalContributions
+        weeks { contributionDays { date contributionCount } }
+      }
+    }
+  }
+}";
+
+#[derive(Debug, Deserialize)]
+struct GraphQlResponse {
+    data: Option<GraphQlData>,
+    errors: Option<Vec<GraphQlError>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GraphQlError {
+    message: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct GraphQlData {
+    user: Option<GraphQlUser>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GraphQlUser {
+    contributions_collection: GraphQlContributionsCollection,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GraphQlContributionsCollection {
+    contribution_calendar: GraphQlContributionCalendar,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GraphQlContributionCalendar {
+    total_contributions: u32,
+    weeks: Vec<GraphQlWeek>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GraphQlWeek {
+    contribution_days: Vec<GraphQlDay>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GraphQlDay {
+    date: String,
+    contribution_count: u32,
+}
+
+/// Scrapes the public `/users/{username}/contributions` page.
+pub struct GitHubScraper {
+    pub base_url: String,
+}
+
+impl ContributionSource for GitHubScraper {
+    async fn fetch(&self, username: &str, range: Option<DateRange>) -> Result<ContributionCalendar> {
+        let mut url = format!("{}/users/{}/contributions", self.base_url, username);
+        if let Some(range) = range {
+            url = format!("{}?from={}&to={}", url, range.from, range.to);
+        }
+        info!("Fetching contributions for user '{}' from {}", username, url);
+        let body = match reqwest::get(&url).await {
+            Ok(resp) => {
+                info!("Successfully fetched page for user '{}'.", username);
+                match resp.text().await {
+                    Ok(text) => text,
+                    Err(e) => {
+                        error!("Failed to read response text for user '{}': {}", username, e);
+                        return Err(anyhow!("Failed to read response text: {}", e));
+                    }
+                }
+            },
+            Err(e) => {
+                error!("Failed to fetch page for user '{}': {}", username, e);
+                return Err(anyhow!("Failed to fetch page: {}", e));
+            }
+        };
+        let document = Html::parse_document(&body);
+
+        // Build a map from td id to tooltip text
+        let tooltip_selector = Selector::parse("tool-tip").unwrap();
+        let mut tooltip_map = HashMap::new();
+        for tooltip in document.select(&tooltip_selector) {
+            if let Some(for_id) = tooltip.value().attr("for") {
+                let text = tooltip.text().collect::<String>().trim().to_string();
+                tooltip_map.insert(for_id.to_string(), text);
+            }
+        }
+
+        // Parse yearly contributions from the h2 element, e.g. "1,234 contributions in the last year"
+        let mut yearly_contributions = None;
+        let mut warnings = Vec::new();
+        if let Ok(h2_selector) = Selector::parse("h2#js-contribution-activity-description")
+            && let Some(h2) = document.select(&h2_selector).next() {
+            let text = h2.text().collect::<String>().split_whitespace().collect::<Vec<_>>().join(" ");
+            yearly_contributions = parse_total(&text);
+            if yearly_contributions.is_none() {
+                warnings.push(ParseWarning::UnreadableTotal { text });
+            }
+        }
+
+        let td_selector = Selector::parse("td.ContributionCalendar-day").unwrap();
+        let mut days: Vec<(String, u32, String)> = Vec::new();
+        for td in document.select(&td_selector) {
+            let date = td.value().attr("data-date").unwrap_or("").to_string();
+            let id = td.value().attr("id").unwrap_or("");
+            let tooltip_text = tooltip_map.get(id);
+            let count = tooltip_text
+                .and_then(|text| parse_contribution_count(text))
+                .unwrap_or(0);
+            let label = tooltip_text.cloned().unwrap_or_default();
+            if !date.is_empty() {
+                days.push((date, count, label));
+            }
+        }
+        retain_range(&mut days, range);
+        Ok(ContributionCalendar { days, yearly_contributions, warnings })
+    }
+}
+
+/// Queries `contributionsCollection.contributionCalendar` through the GraphQL API.
+pub struct GitHubGraphQl {
+    pub base_url: String,
+    pub token: String,
+}
+
+impl GitHubGraphQl {
+    fn graphql_url(&self) -> String {
+        // github.com serves its API from a separate host, Enterprise Server from /api
+        if self.base_url == DEFAULT_GITHUB_URL {
+            "https://api.github.com/graphql".to_string()
+        } else {
+            format!("{}/api/graphql", self.base_url)
+        }
+    }
+}
+
+impl ContributionSource for GitHubGraphQl {
+    async fn fetch(&self, username: &str, range: Option<DateRange>) -> Result<ContributionCalendar> {
+        let url = self.graphql_url();
+        info!("Fetching contributions for user '{}' from {}", username, url);
+        // Left out, from and to default to the past year
+        let mut variables = serde_json::json!({ "login": username });
+        if let Some(range) = range {
+            variables["from"] = format!("{}T00:00:00Z", range.from).into();
+            variables["to"] = format!("{}T23:59:59Z", range.to).into();
+        }
+        let body = serde_json::json!({
+            "query": CONTRIBUTION_CALENDAR_QUERY,
+            "variables": variables,
+        });
+        let response = reqwest::Client::new()
+            .post(&url)
+            .bearer_auth(&self.token)
+            .header(reqwest::header::USER_AGENT, "glance-github-graph")
+            .json(&body)
+            .send()
+            .await
+            .and_then(|resp| resp.error_for_status());
+        let response: GraphQlResponse = match response {
+            Ok(resp) => match resp.json().await {
+                Ok(json) => json,
+                Err(e) => {
+                    error!("Failed to parse GraphQL response for user '{}': {}", username, e);
+                    return Err(anyhow!("Failed to parse GraphQL response: {}", e));
+                }
+            },
+            Err(e) => {
+                error!("Failed to query GraphQL API for user '{}': {}", username, e);
+                return Err(anyhow!("Failed to query GraphQL API: {}", e));
+            }
+        };
+        if let Some(errors) = response.errors.filter(|errors| !errors.is_empty()) {
+            let message = errors.into_iter().map(|e| e.message).collect::<Vec<_>>().join("; ");
+            error!("GraphQL API returned errors for user '{}': {}", username, message);
+            return Err(anyhow!("GraphQL API returned errors: {}", message));
+        }
+        let Some(calendar) = response.data
+            .and_then(|data| data.user)
+            .map(|user| user.contributions_collection.contribution_calendar) else {
+            error!("No contributions found for user {}", username);
+            return Err(anyhow!("No contributions found for user {}", username));
+        };
+
+        let mut days: Vec<(String, u32, String)> = Vec::new();
+        for day in calendar.weeks.into_iter().flat_map(|week| week.contribution_days) {
+            let label = chrono::NaiveDate::parse_from_str(&day.date, "%Y-%m-%d")
+                .map(|date| crate::contribution_label(date, day.contribution_count))
+                .unwrap_or_default();
+            days.push((day.date, day.contribution_count, label));
+        }
+        retain_range(&mut days, range);
+        Ok(ContributionCalendar { days, yearly_contributions: Some(calendar.total_contributions), warnings: Vec::new() })
+    }
+}
+
+/// Drops the padding days a calendar may show around the requested range.
+fn retain_range(days: &mut Vec<(String, u32, String)>, range: Option<DateRange>) {
+    if let Some(range) = range {
+        let (from, to) = (range.from.to_string(), range.to.to_string());
+        days.retain(|(date, _, _)| *date >= from && *date <= to);
+    }
+}
+
+fn parse_contribution_count(text: &str) -> Option<u32> {
+    // Examples: "No contributions on July 14th.", "7 contributions on September 1st.", "1 contribution on November 3rd."
+    if text.starts_with("No contributions") {
+        Some(0)
+    } else {
+        text.split_whitespace().next()?.parse::<u32>().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    const FIXTURE: &str = r#"<html><body>
+<h2 id="js-contribution-activity-description" class="f4 text-normal mb-2">
+  1,234
+  contributions
+  in the last year
+</h2>
+<table><tbody><tr>
+<td id="day-0" class="ContributionCalendar-day" data-date="2024-01-07"></td>
+<td id="day-1" class="ContributionCalendar-day" data-date="2024-01-08"></td>
+<td id="day-2" class="ContributionCalendar-day" data-date="2024-01-09"></td>
+</tr></tbody></table>
+<tool-tip for="day-0">No contributions on January 7th.</tool-tip>
+<tool-tip for="day-1">1 contribution on January 8th.</tool-tip>
+<tool-tip for="day-2">7 contributions on January 9th.</tool-tip>
+</body></html>"#;
+
+    /// Serves `body` to a single request on a local port, returning its base URL
+    /// and the request line it received.
+    async fn serve_once(body: &'static str) -> (String, tokio::task::JoinHandle<String>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let handle = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = vec![0; 4096];
+            let read = socket.read(&mut request).await.unwrap();
+            let request = String::from_utf8_lossy(&request[..read]).to_string();
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body,
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+            request.lines().next().unwrap_or_default().to_string()
+        });
+        (base_url, handle)
+    }
+
+    #[tokio::test]
+    async fn scraper_reads_the_calendar_from_its_base_url() {
+        let (base_url, request) = serve_once(FIXTURE).await;
+        let calendar = GitHubScraper { base_url }.fetch("alice", None).await.unwrap();
+        assert_eq!(request.await.unwrap(), "GET /users/alice/contributions HTTP/1.1");
+        assert_eq!(calendar.yearly_contributions, Some(1234));
+        assert!(calendar.warnings.is_empty());
+        let days: Vec<_> = calendar.days.iter().map(|(date, count, _)| (date.as_str(), *count)).collect();
+        assert_eq!(days, vec![("2024-01-07", 0), ("2024-01-08", 1), ("2024-01-09", 7)]);
+        assert_eq!(calendar.days[2].2, "7 contributions on January 9th.");
+    }
+
+    #[test]
+    fn graphql_url_follows_the_instance() {
+        let graphql = |base_url: &str| GitHubGraphQl { base_url: base_url.to_string(), token: String::new() }.graphql_url();
+        assert_eq!(graphql(DEFAULT_GITHUB_URL), "https://api.github.com/graphql");
+        assert_eq!(graphql("https://github.example.com"), "https://github.example.com/api/graphql");
+    }
+
+    #[test]
+    fn contribution_counts_are_read_from_tooltips() {
+        assert_eq!(parse_contribution_count("No contributions on July 14th."), Some(0));
+        assert_eq!(parse_contribution_count("1 contribution on November 3rd."), Some(1));
+        assert_eq!(parse_contribution_count("7 contributions on September 1st."), Some(7));
+        assert_eq!(parse_contribution_count(""), None);
+    }
+}
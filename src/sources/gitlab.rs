@@ -0,0 +1,39 @@
+use anyhow::{Result, anyhow};
+use std::collections::HashMap;
+use log::{info, error};
+use super::{ContributionCalendar, ContributionSource, DateRange, fill_calendar};
+
+pub const DEFAULT_GITLAB_URL: &str = "https://gitlab.com";
+
+/// Reads the `/users/{username}/calendar.json` endpoint behind GitLab's activity calendar.
+pub struct GitLab {
+    pub base_url: String,
+}
+
+impl ContributionSource for GitLab {
+    async fn fetch(&self, username: &str, range: Option<DateRange>) -> Result<ContributionCalendar> {
+        let url = format!("{}/users/{}/calendar.json", self.base_url, username);
+        info!("Fetching contributions for user '{}' from {}", username, url);
+        let response = match reqwest::get(&url).await.and_then(|resp| resp.error_for_status()) {
+            Ok(resp) => resp,
+            Err(e) => {
+                error!("Failed to fetch calendar for user '{}': {}", username, e);
+                return Err(anyhow!("Failed to fetch calendar: {}", e));
+            }
+        };
+        // Only days with activity are listed, e.g. {"2024-05-01": 3}
+        let calendar: HashMap<String, u32> = match response.json().await {
+            Ok(json) => json,
+            Err(e) => {
+                error!("Failed to parse calendar for user '{}': {}", username, e);
+                return Err(anyhow!("Failed to parse calendar: {}", e));
+            }
+        };
+        let counts = calendar.into_iter()
+            .filter_map(|(date, count)| {
+                chrono::NaiveDate::parse_from_str(&date, "%Y-%m-%d").ok().map(|date| (date, count))
+            })
+            .collect();
+        fill_calendar(counts, range)
+    }
+}
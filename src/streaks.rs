@@ -0,0 +1,193 @@
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Which days without contributions break a streak.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub enum StreakPolicy {
+    /// Every day needs a contribution, today included.
+    #[default]
+    Strict,
+    /// Every day needs a contribution, but today only counts once it's over.
+    Grace,
+    /// Up to this many empty days per week are skipped over, plus grace for today.
+    Skip(u32),
+    /// Empty Saturdays and Sundays are skipped over, plus grace for today.
+    Weekdays,
+}
+
+impl StreakPolicy {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "strict" => Some(StreakPolicy::Strict),
+            "grace" => Some(StreakPolicy::Grace),
+            "weekdays" => Some(StreakPolicy::Weekdays),
+            _ => match value.strip_prefix("skip-")?.parse::<u32>() {
+                Ok(days) if (1..=6).contains(&days) => Some(StreakPolicy::Skip(days)),
+                _ => None,
+            },
+        }
+    }
+
+    pub fn description(&self) -> String {
+        match self {
+            StreakPolicy::Strict => "Every day needs a contribution, today included.".to_string(),
+            StreakPolicy::Grace => "Every day needs a contribution, but today only counts once it's over.".to_string(),
+            StreakPolicy::Skip(1) => "Every day needs a contribution, except for 1 day a week. Today only counts once it's over.".to_string(),
+            StreakPolicy::Skip(days) => format!("Every day needs a contribution, except for {} days a week. Today only counts once it's over.", days),
+            StreakPolicy::Weekdays => "Every weekday needs a contribution, weekends only add to the streak. Today only counts once it's over.".to_string(),
+        }
+    }
+}
+
+impl fmt::Display for StreakPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StreakPolicy::Strict => write!(f, "strict"),
+            StreakPolicy::Grace => write!(f, "grace"),
+            StreakPolicy::Skip(days) => write!(f, "skip-{}", days),
+            StreakPolicy::Weekdays => write!(f, "weekdays"),
+        }
+    }
+}
+
+impl From<StreakPolicy> for String {
+    fn from(policy: StreakPolicy) -> Self {
+        policy.to_string()
+    }
+}
+
+impl TryFrom<String> for StreakPolicy {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::parse(&value).ok_or_else(|| format!("Unknown streak policy '{}'", value))
+    }
+}
+
+/// The current and longest streak of `days`, sorted oldest first, under `policy`,
+/// and the date the current streak started on, empty without one.
+/// Skipped days neither break a streak nor add to it, and a streak that breaks
+/// gets its skips back. Grace only forgives the last day when it's `today`.
+pub fn streaks(days: &[(String, u32, String)], policy: StreakPolicy, today: Option<NaiveDate>) -> (u32, u32, String) {
+    let mut streak = 0;
+    let mut longest = 0;
+    let mut start = 0;
+    // Skips used so far, per week starting on Sunday
+    let mut skipped: HashMap<NaiveDate, u32> = HashMap::new();
+    for (i, (date, count, _)) in days.iter().enumerate() {
+        if *count > 0 {
+            if streak == 0 {
+                start = i;
+            }
+            streak += 1;
+            longest = longest.max(streak);
+            continue;
+        }
+        let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").ok();
+        let is_today = i == days.len() - 1 && date.is_some() && date == today;
+        if is_today && policy != StreakPolicy::Strict {
+            continue;
+        }
+        let skip = match (policy, date) {
+            (StreakPolicy::Weekdays, Some(date)) => matches!(date.weekday(), Weekday::Sat | Weekday::Sun),
+            (StreakPolicy::Skip(allowed), Some(date)) if streak > 0 => {
+                let week = date - Duration::days(date.weekday().num_days_from_sunday() as i64);
+                let used = skipped.entry(week).or_insert(0);
+                *used += 1;
+                *used <= allowed
+            },
+            _ => false,
+        };
+        if !skip {
+            streak = 0;
+            skipped.clear();
+        }
+    }
+    let start = if streak > 0 { days[start].0.clone() } else { String::new() };
+    (streak, longest, start)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_support::{date, days_from};
+
+    /// Consecutive days starting on `first`, along with the last one.
+    fn calendar(first: &str, counts: &[u32]) -> (Vec<(String, u32, String)>, NaiveDate) {
+        let days = days_from(first, counts);
+        let last = date(&days.last().unwrap().0);
+        (days, last)
+    }
+
+    #[test]
+    fn parse_round_trips_every_policy() {
+        for value in ["strict", "grace", "weekdays", "skip-1", "skip-6"] {
+            assert_eq!(StreakPolicy::parse(value).unwrap().to_string(), value);
+        }
+        for value in ["skip-0", "skip-7", "skip-", "skip-x", "lenient"] {
+            assert_eq!(StreakPolicy::parse(value), None);
+        }
+        assert_eq!(StreakPolicy::default(), StreakPolicy::Strict);
+    }
+
+    #[test]
+    fn strict_breaks_on_an_empty_today() {
+        let (days, today) = calendar("2024-01-08", &[1, 1, 1]);
+        assert_eq!(streaks(&days, StreakPolicy::Strict, Some(today)), (3, 3, "2024-01-08".to_string()));
+        let (days, today) = calendar("2024-01-08", &[1, 1, 0]);
+        assert_eq!(streaks(&days, StreakPolicy::Strict, Some(today)), (0, 2, String::new()));
+    }
+
+    #[test]
+    fn grace_only_forgives_today() {
+        let (days, today) = calendar("2024-01-08", &[1, 1, 0]);
+        assert_eq!(streaks(&days, StreakPolicy::Grace, Some(today)), (2, 2, "2024-01-08".to_string()));
+        let (days, today) = calendar("2024-01-08", &[1, 0, 0]);
+        assert_eq!(streaks(&days, StreakPolicy::Grace, Some(today)), (0, 1, String::new()));
+    }
+
+    #[test]
+    fn grace_does_not_apply_to_the_end_of_a_past_range() {
+        let (days, _) = calendar("2023-12-29", &[1, 1, 0]);
+        assert_eq!(streaks(&days, StreakPolicy::Grace, Some(date("2024-06-01"))), (0, 2, String::new()));
+        assert_eq!(streaks(&days, StreakPolicy::Grace, None), (0, 2, String::new()));
+    }
+
+    #[test]
+    fn skip_allows_n_empty_days_per_week() {
+        // Sunday to Saturday: one skip is fine, a second in the same week breaks the streak
+        let (days, today) = calendar("2024-01-07", &[1, 0, 1, 1, 1, 1, 1]);
+        assert_eq!(streaks(&days, StreakPolicy::Skip(1), Some(today)), (6, 6, "2024-01-07".to_string()));
+        let (days, today) = calendar("2024-01-07", &[1, 0, 1, 0, 1, 1, 1]);
+        assert_eq!(streaks(&days, StreakPolicy::Skip(1), Some(today)), (3, 3, "2024-01-11".to_string()));
+    }
+
+    #[test]
+    fn skip_counts_start_over_when_a_streak_breaks() {
+        // Two skips, a third empty day breaks the streak, and the new one gets its own skips
+        let (days, today) = calendar("2024-01-07", &[1, 0, 1, 0, 0, 1, 0, 1]);
+        assert_eq!(streaks(&days, StreakPolicy::Skip(2), Some(today)), (2, 2, "2024-01-12".to_string()));
+    }
+
+    #[test]
+    fn skips_are_not_spent_before_a_streak_starts() {
+        let (days, today) = calendar("2024-01-07", &[0, 0, 1, 0, 1]);
+        assert_eq!(streaks(&days, StreakPolicy::Skip(1), Some(today)), (2, 2, "2024-01-09".to_string()));
+    }
+
+    #[test]
+    fn weekdays_skip_empty_weekends() {
+        // Friday, an empty weekend, then Monday
+        let (days, today) = calendar("2024-01-12", &[1, 0, 0, 1]);
+        assert_eq!(streaks(&days, StreakPolicy::Weekdays, Some(today)), (2, 2, "2024-01-12".to_string()));
+        // An empty Thursday still breaks it
+        let (days, today) = calendar("2024-01-11", &[0, 1, 0, 0, 1]);
+        assert_eq!(streaks(&days, StreakPolicy::Weekdays, Some(today)), (2, 2, "2024-01-12".to_string()));
+        let (days, today) = calendar("2024-01-10", &[1, 0, 1, 0, 0, 1]);
+        assert_eq!(streaks(&days, StreakPolicy::Weekdays, Some(today)), (2, 2, "2024-01-12".to_string()));
+    }
+}
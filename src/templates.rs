@@ -1,20 +1,59 @@
 use askama::Template;
+use crate::levels::Encoding;
 
 pub struct GraphCell {
     pub date: String,
     pub count: u32,
+    pub level: usize,
     pub col: usize,
     pub row: usize,
     pub color: String,
     pub hover_text: String,
 }
 
+/// Fill pattern marking one level, for the dots and hatch encodings.
+pub struct LevelPattern {
+    pub id: String,
+    pub level: usize,
+    pub spacing: f32,
+    pub color: String,
+}
+
+/// One number in the stats widget.
+pub struct StatTile {
+    pub value: String,
+    pub label: String,
+    pub title: Option<String>,
+}
+
 #[derive(Template)]
 #[template(path = "stats.html")]
-pub struct ContributionStatsTemplate<'a> {
-    pub stats: &'a crate::ContributionStats,
-    pub show_quartiles: bool,
-    pub quartiles_string: String,
+pub struct ContributionStatsTemplate {
+    pub rows: Vec<Vec<StatTile>>,
+    pub compact: bool,
+}
+
+pub struct LeaderboardEntry {
+    pub rank: Option<usize>,
+    pub username: String,
+    pub url: String,
+    pub value: String,
+    pub error: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "leaderboard.html")]
+pub struct LeaderboardTemplate {
+    pub metric_label: &'static str,
+    pub leaderboard: Vec<LeaderboardEntry>,
+}
+
+#[derive(Template)]
+#[template(path = "team_stats.html")]
+pub struct TeamStatsTemplate {
+    pub rows: Vec<Vec<StatTile>>,
+    pub compact: bool,
+    pub leaderboard: Vec<LeaderboardEntry>,
 }
 
 #[derive(Template)]
@@ -23,15 +62,24 @@ pub struct ContributionSvgGraphTemplate<'a> {
     pub stats: &'a crate::ContributionStats,
     pub max_count: u32,
     pub cells: Vec<GraphCell>,
+    /// Number of week columns the cells span, usually 53 but 54 for some ranges.
+    pub weeks: usize,
     pub show_months: bool,
     pub svg_height: String,
     pub font_size: String,
     pub show_weekdays: bool,
     pub primary_color: String,
     pub color_shades: Vec<String>,
+    pub label_color: String,
     pub month_labels: Vec<(usize, String)>,
     pub weekday_labels: Vec<(usize, &'static str)>,
     pub cell_radius: u32,
+    pub encoding: Encoding,
+    /// `(inset, size)` of the square drawn for each level with the size encoding.
+    pub level_marks: Vec<(f32, f32)>,
+    pub patterns: Vec<LevelPattern>,
+    pub aria_label: String,
+    pub description: String,
 }
 
 #[derive(Template)]
@@ -39,5 +87,21 @@ pub struct ContributionSvgGraphTemplate<'a> {
 pub struct ContributionGraphHtmlTemplate<'a> {
     pub svg: ContributionSvgGraphTemplate<'a>,
     pub quartiles: String,
+    /// Users left out of the graph, with their error.
+    pub failed: Vec<(String, String)>,
+}
+
+
+pub struct YearGraph {
+    pub year: i32,
+    pub total: u32,
+    pub svg: String,
 }
 
+#[derive(Template)]
+#[template(path = "graph_years.html")]
+pub struct ContributionYearsGraphTemplate<'a> {
+    pub years: Vec<YearGraph>,
+    /// Stats over every year together.
+    pub stats: &'a crate::ContributionStats,
+}
@@ -0,0 +1,43 @@
+//! Calendars for the unit tests, so each module doesn't build its own.
+
+use chrono::NaiveDate;
+use crate::{compute_stats, contribution_label, ContributionStats, StreakPolicy};
+
+pub fn date(value: &str) -> NaiveDate {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d").unwrap()
+}
+
+/// Days on the given dates, labelled the way GitHub does.
+pub fn days(days: &[(&str, u32)]) -> Vec<(String, u32, String)> {
+    days.iter()
+        .map(|(day, count)| (day.to_string(), *count, contribution_label(date(day), *count)))
+        .collect()
+}
+
+/// Consecutive days starting on `first`, one for each count.
+pub fn days_from(first: &str, counts: &[u32]) -> Vec<(String, u32, String)> {
+    date(first).iter_days()
+        .zip(counts)
+        .map(|(day, count)| (day.format("%Y-%m-%d").to_string(), *count, contribution_label(day, *count)))
+        .collect()
+}
+
+fn strict_stats(days: Vec<(String, u32, String)>) -> ContributionStats {
+    let total = days.iter().map(|(_, c, _)| c).sum();
+    compute_stats("alice", days, total, StreakPolicy::Strict)
+}
+
+/// Stats over the given dates under the strict policy, totalled from the days.
+pub fn stats(values: &[(&str, u32)]) -> ContributionStats {
+    strict_stats(days(values))
+}
+
+/// Stats over consecutive days starting on `first`, like [`stats`].
+pub fn stats_from(first: &str, counts: &[u32]) -> ContributionStats {
+    strict_stats(days_from(first, counts))
+}
+
+/// The date and count of every day in `stats`.
+pub fn counts(stats: &ContributionStats) -> Vec<(&str, u32)> {
+    stats.daily_contributions.iter().map(|(day, count, _)| (day.as_str(), *count)).collect()
+}
@@ -0,0 +1,62 @@
+use anyhow::{Result, anyhow};
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use std::sync::RwLock;
+use log::info;
+use crate::color::Color;
+
+/// Follows the Glance dashboard theme through its CSS variables instead of fixed colors.
+pub const GLANCE_THEME: &str = "glance";
+
+/// Built-in palettes, listed from the background (no contributions) to the busiest shade.
+const BUILTIN_THEMES: &[(&str, &[&str])] = &[
+    ("github-light", &["#ebedf0", "#9be9a8", "#40c463", "#30a14e", "#216e39"]),
+    ("github-dark", &["#161b22", "#0e4429", "#006d32", "#26a641", "#39d353"]),
+    ("halloween", &["#ebedf0", "#ffee4a", "#ffc501", "#fe9600", "#03001c"]),
+    ("dracula", &["#282a36", "#44475a", "#6272a4", "#bd93f9", "#ff79c6"]),
+    ("nord", &["#3b4252", "#5e81ac", "#81a1c1", "#88c0d0", "#8fbcbb"]),
+    ("catppuccin", &["#313244", "#4b6e52", "#6a9a6f", "#88c08a", "#a6e3a1"]),
+    ("solarized", &["#eee8d5", "#859900"]),
+    ("solarized-dark", &["#073642", "#859900"]),
+    // Colorblind-safe: lightness rises steadily, so every level stays apart without telling hues apart
+    ("viridis", &["#440154", "#3b528b", "#21918c", "#5ec962", "#fde725"]),
+    ("cividis", &["#00204d", "#414d6b", "#7c7b78", "#bcaf6f", "#ffea46"]),
+];
+
+lazy_static! {
+    static ref CUSTOM_THEMES: RwLock<HashMap<String, Vec<String>>> = RwLock::new(HashMap::new());
+}
+
+/// Looks up a palette by name. Themes loaded from a file take precedence over built-ins.
+pub fn get(name: &str) -> Option<Vec<String>> {
+    if let Some(palette) = CUSTOM_THEMES.read().unwrap().get(name) {
+        return Some(palette.clone());
+    }
+    BUILTIN_THEMES.iter()
+        .find(|(theme, _)| *theme == name)
+        .map(|(_, palette)| palette.iter().map(|c| c.to_string()).collect())
+}
+
+/// Adds the themes from a JSON or TOML file mapping names to palettes, e.g.
+/// `{"ocean": ["#0b1d2a", "#1f6f8b", "#99d6ea"]}` or `ocean = ["#0b1d2a", "#1f6f8b", "#99d6ea"]`.
+pub fn load_file(path: &str) -> Result<usize> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| anyhow!("Failed to read themes file '{}': {}", path, e))?;
+    let themes: HashMap<String, Vec<String>> = if path.ends_with(".toml") {
+        toml::from_str(&contents).map_err(|e| anyhow!("Failed to parse themes file '{}': {}", path, e))?
+    } else {
+        serde_json::from_str(&contents).map_err(|e| anyhow!("Failed to parse themes file '{}': {}", path, e))?
+    };
+    for (name, palette) in &themes {
+        if palette.len() < 2 {
+            return Err(anyhow!("Theme '{}' needs at least 2 colors", name));
+        }
+        for color in palette {
+            Color::parse(color).map_err(|e| anyhow!("Theme '{}' has an {}", name, e))?;
+        }
+    }
+    let count = themes.len();
+    info!("Loaded {} themes from {}", count, path);
+    CUSTOM_THEMES.write().unwrap().extend(themes);
+    Ok(count)
+}
@@ -0,0 +1,151 @@
+use crate::ContributionStats;
+use crate::templates::StatTile;
+
+/// A number shown in the stats widget.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tile {
+    Today,
+    Year,
+    Streak,
+    Longest,
+    HighScore,
+    Avg,
+    Median,
+    Active,
+    Gap,
+    StreakStart,
+    BestDay,
+    BestMonth,
+    Week,
+    Month,
+    Quartiles,
+}
+
+/// The row shown without a `tiles` parameter.
+pub const DEFAULT_TILES: [Tile; 4] = [Tile::Today, Tile::Year, Tile::Streak, Tile::Longest];
+/// The rows added by `show_insights`.
+pub const INSIGHT_TILES: [[Tile; 4]; 2] = [
+    [Tile::Avg, Tile::Active, Tile::Gap, Tile::StreakStart],
+    [Tile::BestDay, Tile::BestMonth, Tile::Week, Tile::Month],
+];
+
+impl Tile {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "today" => Some(Tile::Today),
+            "year" => Some(Tile::Year),
+            "streak" => Some(Tile::Streak),
+            "longest" => Some(Tile::Longest),
+            "high-score" => Some(Tile::HighScore),
+            "avg" => Some(Tile::Avg),
+            "median" => Some(Tile::Median),
+            "active" => Some(Tile::Active),
+            "gap" => Some(Tile::Gap),
+            "streak-start" => Some(Tile::StreakStart),
+            "best-day" => Some(Tile::BestDay),
+            "best-month" => Some(Tile::BestMonth),
+            "week" => Some(Tile::Week),
+            "month" => Some(Tile::Month),
+            "quartiles" => Some(Tile::Quartiles),
+            _ => None,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Tile::Today => "Today",
+            Tile::Year => "Year",
+            Tile::Streak => "Streak",
+            Tile::Longest => "Top Streak",
+            Tile::HighScore => "High Score",
+            Tile::Avg => "Avg",
+            Tile::Median => "Median",
+            Tile::Active => "Active",
+            Tile::Gap => "Longest Gap",
+            Tile::StreakStart => "Streak Since",
+            Tile::BestDay => "Busiest Day",
+            Tile::BestMonth => "Busiest Month",
+            Tile::Week => "Week",
+            Tile::Month => "30 Days",
+            Tile::Quartiles => "Quartiles",
+        }
+    }
+
+    /// The tile's value and tooltip for `stats`, with `-` for values that don't exist yet.
+    pub fn render(&self, stats: &ContributionStats, label: &str) -> StatTile {
+        let insights = &stats.insights;
+        let or_dash = |value: String| if value.is_empty() { "-".to_string() } else { value };
+        let (value, title) = match self {
+            Tile::Today => (stats.today.to_string(), None),
+            Tile::Year => {
+                let warnings = stats.warnings.iter().map(|w| w.to_string()).collect::<Vec<_>>().join("\n");
+                (stats.yearly_contributions.to_string(), Some(warnings).filter(|w| !w.is_empty()))
+            },
+            Tile::Streak | Tile::Longest => {
+                let value = if *self == Tile::Streak { stats.current_streak } else { stats.longest_streak };
+                (value.to_string(), Some(stats.streak_policy.description()))
+            },
+            Tile::HighScore => (stats.high_score.score.to_string(), Some(format!("On {}", stats.high_score.date)).filter(|_| !stats.high_score.date.is_empty())),
+            Tile::Avg => (format!("{:.1}", insights.mean_per_active_day), Some("Mean contributions per day with contributions".to_string())),
+            Tile::Median => (format!("{:.1}", insights.median_per_active_day), Some("Median contributions per day with contributions".to_string())),
+            Tile::Active => (format!("{:.0}%", insights.active_day_percentage), Some(format!("{} days with contributions", insights.active_days))),
+            Tile::Gap => (insights.longest_gap.to_string(), Some("Most days in a row without contributions".to_string())),
+            Tile::StreakStart => (or_dash(stats.current_streak_start.clone()), Some("Start of the current streak".to_string())),
+            Tile::BestDay => (or_dash(insights.busiest_weekday.clone()), None),
+            Tile::BestMonth => (or_dash(insights.busiest_month_label()), None),
+            Tile::Week => (insights.week_over_week.label(), Some("The last 7 days against the 7 before them".to_string())),
+            Tile::Month => (insights.month_over_month.label(), Some("The last 30 days against the 30 before them".to_string())),
+            Tile::Quartiles => (stats.quartiles.iter().map(|q| q.to_string()).collect::<Vec<_>>().join(" "), None),
+        };
+        StatTile { value, label: label.to_string(), title }
+    }
+}
+
+/// Parses a comma separated list of tiles, e.g. `today,year,streak`.
+pub fn parse_tiles(value: &str) -> Result<Vec<Tile>, String> {
+    value.split(',')
+        .map(str::trim)
+        .filter(|tile| !tile.is_empty())
+        .map(|tile| Tile::parse(tile).ok_or_else(|| format!("Unknown tile '{}'", tile)))
+        .collect()
+}
+
+/// Renders `tiles`, labelling them with `labels` in the same order. Missing or
+/// empty labels fall back to the tile's own.
+pub fn render_tiles(stats: &ContributionStats, tiles: &[Tile], labels: &[&str]) -> Vec<StatTile> {
+    tiles.iter().enumerate()
+        .map(|(i, tile)| {
+            let label = labels.get(i).copied().filter(|label| !label.is_empty()).unwrap_or(tile.label());
+            tile.render(stats, label)
+        })
+        .collect()
+}
+
+/// Which tiles the stats widget shows, and how.
+#[derive(Debug, Clone)]
+pub struct StatsLayout {
+    pub tiles: Vec<Tile>,
+    pub labels: Vec<String>,
+    pub show_quartiles: bool,
+    pub show_insights: bool,
+    /// Puts every tile on a single row.
+    pub compact: bool,
+}
+
+impl StatsLayout {
+    pub fn rows(&self, stats: &ContributionStats) -> Vec<Vec<StatTile>> {
+        let labels: Vec<&str> = self.labels.iter().map(String::as_str).collect();
+        let mut rows = vec![render_tiles(stats, &self.tiles, &labels)];
+        if self.show_quartiles {
+            rows.push(render_tiles(stats, &[Tile::Quartiles], &[]));
+        }
+        if self.show_insights {
+            rows.extend(INSIGHT_TILES.iter().map(|row| render_tiles(stats, row, &[])));
+        }
+        if self.compact {
+            rows = vec![rows.into_iter().flatten().collect()];
+        }
+        rows.retain(|row| !row.is_empty());
+        rows
+    }
+}